@@ -6,8 +6,138 @@ pub enum Error {
     Generic(String), // For beginning only
     #[error(transparent)]
     IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
     #[error("No puzzles found in the file")]
     NoPuzzlesFound,
+    #[error("Unknown strategy '{0}'")]
+    UnknownStrategy(String),
+    #[error("Unknown format '{name}', expected auto or one of {known}")]
+    UnknownFormat { name: String, known: String },
     #[error("Failed to randomly select a puzzle")]
     RandomSelectionFailed,
+    #[error("Contradiction: {0}")]
+    Contradiction(crate::puzzle::contradiction::Contradiction),
+}
+
+/// Where in an input a parse error was found. Lines and columns count from 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub path: String,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.path, self.line, self.column)
+    }
+}
+
+/// A puzzle source that doesn't match its format, with the offending text.
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("{at}: row {row} of {puzzle} has {found} cells instead of {expected}: '{text}'")]
+    RowLength {
+        at: Location,
+        puzzle: String,
+        row: usize,
+        found: usize,
+        expected: usize,
+        text: String,
+    },
+    #[error("{at}: {puzzle} ends after {found} of {expected} rows")]
+    MissingRows {
+        at: Location,
+        puzzle: String,
+        found: usize,
+        expected: usize,
+    },
+    #[error("{at}: expected 16, 36, 81, 144, 256 or 625 cells, found {found}: '{text}'")]
+    CellCount {
+        at: Location,
+        found: usize,
+        text: String,
+    },
+    #[error("{at}: unexpected character '{found}' in '{text}'")]
+    UnknownCharacter {
+        at: Location,
+        found: char,
+        text: String,
+    },
+    #[error("{at}: digit '{found}' is too large for a {size}x{size} grid in '{text}'")]
+    DigitOutOfRange {
+        at: Location,
+        found: char,
+        size: usize,
+        text: String,
+    },
+    #[error("{at}: line is not a puzzle, header or row: '{text}'")]
+    UnexpectedLine { at: Location, text: String },
+    #[error("{at}: unknown header tag '{text}'")]
+    UnknownTag { at: Location, text: String },
+    #[error("{at}: boxes of {shape} don't fit the {size}x{size} grid of {puzzle}")]
+    BoxShape {
+        at: Location,
+        puzzle: String,
+        shape: String,
+        size: usize,
+    },
+    #[error("{at}: {puzzle} is a {variation} puzzle, which needs a 9x9 grid, not {size}x{size}")]
+    UnsupportedSize {
+        at: Location,
+        puzzle: String,
+        variation: String,
+        size: usize,
+    },
+    #[error("{at}: {puzzle} is tagged {variation} but has no {missing}")]
+    MissingConstraints {
+        at: Location,
+        puzzle: String,
+        variation: String,
+        missing: String,
+    },
+    #[error("{at}: {puzzle} is a killer puzzle but its cage map ends after {found} of 10 lines")]
+    MissingCages {
+        at: Location,
+        puzzle: String,
+        found: usize,
+    },
+    #[error("{at}: cage {cage} {reason}")]
+    InvalidCage {
+        at: Location,
+        cage: String,
+        reason: String,
+    },
+    #[error("{at}: {puzzle} is a sandwich puzzle but ends after {found} of 2 clue lines")]
+    MissingClues {
+        at: Location,
+        puzzle: String,
+        found: usize,
+    },
+    #[error("{at}: sandwich clue '{text}' {reason}")]
+    InvalidClue {
+        at: Location,
+        text: String,
+        reason: String,
+    },
+    #[error(
+        "{at}: {puzzle} is a jigsaw puzzle but its region map ends after {found} of {expected} rows"
+    )]
+    MissingRegions {
+        at: Location,
+        puzzle: String,
+        found: usize,
+        expected: usize,
+    },
+    #[error("{at}: region {region} {reason}")]
+    InvalidRegion {
+        at: Location,
+        region: String,
+        reason: String,
+    },
+    #[error("{at}: invalid JSON: {message}")]
+    Json { at: Location, message: String },
 }
@@ -2,38 +2,271 @@
 
 use crate::prelude::*;
 
-use std::time::Instant;
+use clap::{arg, command, value_parser};
+use std::process::ExitCode;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 mod error;
 mod prelude;
 mod puzzle;
 mod utils;
 
-fn main() -> Result<()> {
-    let (successes, failures) = solve_all_puzzles("sudoku.txt")?;
-    println!("Solved: {}, Failed: {}", successes, failures);
-    Ok(())
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            // Parse errors read as `path:line:column: message`
+            eprintln!("Error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
-pub fn solve_all_puzzles(filename: &str) -> Result<(usize, usize)> {
-    let start_time = Instant::now();
-    let puzzles = read_sudoku_puzzles(filename)?;
-    let mut success_count = 0;
-    let mut failure_count = 0;
+fn run() -> Result<()> {
+    let matches = command!()
+        .arg(arg!([FILE] "Puzzle file to solve, or - for stdin").default_value("sudoku.txt"))
+        .arg(
+            arg!(--bench <PASSES> "Time solving every puzzle in FILE over several passes")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(
+            arg!(--backend <BACKEND> "Search used when logic alone can't finish: backtracking or dlx, by default backtracking on 9x9 boards and dlx on larger ones")
+                .value_parser(value_parser!(Backend)),
+        )
+        .arg(
+            arg!(--disable <TECHNIQUES> "Comma-separated techniques to leave out, e.g. x_wing,y_wing")
+                .value_delimiter(','),
+        )
+        .arg(
+            arg!(--jobs <WORKERS> "Solve puzzles in parallel on WORKERS threads, 0 for one per core")
+                .value_parser(value_parser!(usize)),
+        )
+        .arg(arg!(--"check-unique" "Report puzzles in FILE that don't have exactly one solution"))
+        .arg(arg!(--explain "Print every logical step used to solve each puzzle"))
+        .arg(arg!(--rate "Rate every puzzle in FILE by the hardest technique it needs"))
+        .arg(
+            arg!(--format <FORMAT> "Format of FILE: auto, grid, line, sdk or json")
+                .default_value("auto"),
+        )
+        .arg(arg!(--strict "Reject characters in FILE that aren't digits or '.' instead of reading them as blanks"))
+        .arg(arg!(--export <OUTPUT> "Write the puzzles in FILE to stdout as grid, line, sdk or json"))
+        .get_matches();
 
-    for mut puzzle in puzzles {
-        if puzzle.solve() {
-            success_count += 1;
+    let filename = matches.get_one::<String>("FILE").unwrap();
+    let options = ReadOptions {
+        format: matches.get_one::<String>("format").unwrap().clone(),
+        strict: matches.get_flag("strict"),
+    };
+    let mut solver = Solver::new();
+    if let Some(&backend) = matches.get_one::<Backend>("backend") {
+        solver = solver.backend(backend);
+    }
+    for name in matches.get_many::<String>("disable").unwrap_or_default() {
+        solver.disable(name)?;
+    }
+
+    if matches.get_flag("check-unique") {
+        return check_unique_solutions(filename, &options);
+    }
+
+    if matches.get_flag("explain") {
+        return explain_puzzles(filename, &options, &solver);
+    }
+
+    if let Some(output) = matches.get_one::<String>("export") {
+        return export_puzzles(filename, &options, output);
+    }
+
+    if matches.get_flag("rate") {
+        return rate_puzzles(filename, &options);
+    }
+
+    if let Some(&passes) = matches.get_one::<usize>("bench") {
+        return bench_puzzles(filename, &options, passes, &solver);
+    }
+
+    if let Some(&workers) = matches.get_one::<usize>("jobs") {
+        return solve_puzzles_parallel(filename, &options, solver, workers);
+    }
+
+    let start_time = Instant::now();
+    let (mut successes, mut failures) = (0, 0);
+    for result in solve_all_puzzles(filename, &options, &solver)? {
+        let (name, report) = result?;
+        println!("{}: {}", name, report);
+        if report.is_solved() {
+            successes += 1;
         } else {
-            failure_count += 1;
+            failures += 1;
         }
     }
     let elapsed_time = start_time.elapsed();
+    if successes + failures == 0 {
+        return Err(Error::NoPuzzlesFound);
+    }
 
     println!(
         "All Puzzles in {:.4} ms",
         elapsed_time.as_secs_f64() * 1000.0
     );
+    println!("Solved: {}, Failed: {}", successes, failures);
+    Ok(())
+}
 
-    Ok((success_count, failure_count))
+/// Solves the puzzles in the file as they are read, yielding each puzzle's name and report
+/// in file order. Only one puzzle is held in memory at a time.
+pub fn solve_all_puzzles<'a>(
+    filename: &str,
+    options: &ReadOptions,
+    solver: &'a Solver,
+) -> Result<impl Iterator<Item = Result<(String, SolveReport)>> + 'a> {
+    Ok(open_puzzles(filename, options)?.map(move |puzzle| {
+        let mut puzzle = puzzle?;
+        let report = puzzle.solve_using(solver);
+        Ok((puzzle.name().to_string(), report))
+    }))
+}
+
+/// Solves every puzzle in the file across `workers` threads as it is read, printing each
+/// result in file order followed by the batch throughput.
+pub fn solve_puzzles_parallel(
+    filename: &str,
+    options: &ReadOptions,
+    solver: Solver,
+    workers: usize,
+) -> Result<()> {
+    let puzzles = open_puzzles(filename, options)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let batch = runtime.block_on(solve_batch(
+        puzzles,
+        Arc::new(solver),
+        workers,
+        |name, report| println!("{}: {}", name, report),
+    ))?;
+    if batch.puzzles == 0 {
+        return Err(Error::NoPuzzlesFound);
+    }
+
+    println!(
+        "Batch: {} puzzles on {} workers in {:.4} ms, {:.1} puzzles/s, {:.4} ms solving",
+        batch.puzzles,
+        batch.workers,
+        batch.elapsed.as_secs_f64() * 1000.0,
+        batch.puzzles_per_second(),
+        batch.solve_time().as_secs_f64() * 1000.0
+    );
+    println!("Solved: {}, Failed: {}", batch.solved(), batch.failed());
+    Ok(())
+}
+
+/// Solves every puzzle in the file `passes` times and reports the average time per pass and per puzzle.
+/// The file is streamed again for each pass, and only solving is timed.
+pub fn bench_puzzles(
+    filename: &str,
+    options: &ReadOptions,
+    passes: usize,
+    solver: &Solver,
+) -> Result<()> {
+    let passes = passes.max(1);
+
+    let mut count = 0;
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..passes {
+        count = 0;
+        for puzzle in open_puzzles(filename, options)? {
+            let mut puzzle = puzzle?;
+            let start_time = Instant::now();
+            puzzle.solve_using(solver);
+            elapsed += start_time.elapsed();
+            count += 1;
+        }
+    }
+    if count == 0 {
+        return Err(Error::NoPuzzlesFound);
+    }
+    let elapsed_ms = elapsed.as_secs_f64() * 1000.0;
+
+    println!(
+        "Bench: {} puzzles x {} passes, {:.4} ms per pass, {:.4} ms per puzzle",
+        count,
+        passes,
+        elapsed_ms / passes as f64,
+        elapsed_ms / (passes * count) as f64
+    );
+    Ok(())
+}
+
+/// Solves every puzzle in the file and prints the steps taken, one per line.
+pub fn explain_puzzles(filename: &str, options: &ReadOptions, solver: &Solver) -> Result<()> {
+    let mut count = 0;
+    for puzzle in open_puzzles(filename, options)? {
+        let mut puzzle = puzzle?;
+        puzzle.solve_using(solver);
+        println!("Puzzle: {}", puzzle.name());
+        print!("{}", puzzle.board().explain_trace());
+        count += 1;
+    }
+    if count == 0 {
+        return Err(Error::NoPuzzlesFound);
+    }
+    Ok(())
+}
+
+/// Prints each puzzle's rating score, hardest technique and difficulty, or that it's invalid
+/// when its givens can't be solved.
+pub fn rate_puzzles(filename: &str, options: &ReadOptions) -> Result<()> {
+    let mut count = 0;
+    for puzzle in open_puzzles(filename, options)? {
+        let puzzle = puzzle?;
+        match puzzle.rate() {
+            Some(rating) => println!(
+                "{}: {:.1} {:?} ({})",
+                puzzle.name(),
+                rating.score,
+                rating.difficulty,
+                rating.hardest
+            ),
+            None => println!("{}: invalid", puzzle.name()),
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return Err(Error::NoPuzzlesFound);
+    }
+    Ok(())
+}
+
+/// Writes every puzzle in the file to stdout in the `output` format.
+pub fn export_puzzles(filename: &str, options: &ReadOptions, output: &str) -> Result<()> {
+    let registry = FormatRegistry::new();
+    let output = registry.get(output)?;
+    let mut puzzles = open_puzzles(filename, options)?.peekable();
+    if puzzles.peek().is_none() {
+        return Err(Error::NoPuzzlesFound);
+    }
+    output.write(&mut std::io::stdout().lock(), Box::new(puzzles))
+}
+
+/// Lists every puzzle in the file with no solution or more than one.
+pub fn check_unique_solutions(filename: &str, options: &ReadOptions) -> Result<()> {
+    let (mut count, mut malformed) = (0, 0);
+
+    for puzzle in open_puzzles(filename, options)? {
+        let puzzle = puzzle?;
+        count += 1;
+        match puzzle.count_solutions(2).len() {
+            0 => println!("{}: no solution", puzzle.name()),
+            1 => continue,
+            _ => println!("{}: multiple solutions", puzzle.name()),
+        }
+        malformed += 1;
+    }
+
+    if count == 0 {
+        return Err(Error::NoPuzzlesFound);
+    }
+
+    println!("Unique: {}, Malformed: {}", count - malformed, malformed);
+    Ok(())
 }
@@ -1,12 +1,26 @@
 //! Crate prelude
 
-pub use crate::error::Error;
+pub use crate::error::{Error, Location, ParseError};
 
 pub type Result<T> = core::result::Result<T, Error>;
 
 // Generic Wrapper tuple strcut for newtype pattern
 pub struct W<T>(pub T);
 
+pub use crate::puzzle::batch::*;
+pub use crate::puzzle::contradiction::*;
+pub use crate::puzzle::dlx::*;
+pub use crate::puzzle::export::*;
+pub use crate::puzzle::format::*;
 pub use crate::puzzle::import::*;
+pub use crate::puzzle::jigsaw::*;
+pub use crate::puzzle::killer::*;
+pub use crate::puzzle::metadata::*;
+pub use crate::puzzle::rate::*;
+pub use crate::puzzle::report::*;
+pub use crate::puzzle::sandwich::*;
 pub use crate::puzzle::solve::*;
+pub use crate::puzzle::strategy::*;
+pub use crate::puzzle::trace::*;
 pub use crate::puzzle::*;
+pub use crate::utils::*;
@@ -0,0 +1,147 @@
+//! Parallel solving of puzzle collections
+
+use crate::prelude::*;
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+/// Totals of a batch solve, with wall-clock timing for throughput.
+pub struct BatchReport {
+    /// Puzzles solved, whatever the outcome.
+    pub puzzles: usize,
+    pub workers: usize,
+    /// Wall-clock time for the whole batch.
+    pub elapsed: Duration,
+    solved: usize,
+    solve_time: Duration,
+}
+
+impl BatchReport {
+    pub fn solved(&self) -> usize {
+        self.solved
+    }
+
+    pub fn failed(&self) -> usize {
+        self.puzzles - self.solved
+    }
+
+    /// Puzzles finished per second of wall-clock time.
+    pub fn puzzles_per_second(&self) -> f64 {
+        self.puzzles as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Time spent solving summed over every puzzle, which exceeds `elapsed` when workers overlap.
+    pub fn solve_time(&self) -> Duration {
+        self.solve_time
+    }
+}
+
+/// Number of workers to use when asked for `requested`, with 0 meaning one per core.
+pub fn worker_count(requested: usize) -> usize {
+    match requested {
+        0 => thread::available_parallelism().map_or(1, |n| n.get()),
+        n => n,
+    }
+}
+
+/// Solves the puzzles on `workers` blocking tasks (0 for one per core), passing each puzzle's
+/// name and report to `on_result` in input order.
+///
+/// Puzzles are read as workers free up, through a bounded channel, and at most a few per
+/// worker are read ahead of the oldest unfinished one, so memory stays flat however many
+/// puzzles there are. A read error stops reading; the puzzles before it are still reported.
+pub async fn solve_batch(
+    puzzles: impl Iterator<Item = Result<Puzzle>>,
+    solver: Arc<Solver>,
+    workers: usize,
+    mut on_result: impl FnMut(&str, &SolveReport),
+) -> Result<BatchReport> {
+    let start_time = Instant::now();
+    let workers = worker_count(workers);
+    let read_ahead = workers * 16;
+
+    let (puzzle_tx, puzzle_rx) = mpsc::channel::<(usize, Puzzle)>(workers * 2);
+    let puzzle_rx = Arc::new(Mutex::new(puzzle_rx));
+    let (result_tx, mut result_rx) = mpsc::unbounded_channel();
+    let tasks: Vec<_> = (0..workers)
+        .map(|_| {
+            let puzzle_rx = Arc::clone(&puzzle_rx);
+            let result_tx = result_tx.clone();
+            let solver = Arc::clone(&solver);
+            tokio::task::spawn_blocking(move || loop {
+                let next = puzzle_rx.lock().unwrap().blocking_recv();
+                let Some((index, mut puzzle)) = next else {
+                    break;
+                };
+                let report = puzzle.solve_using(&solver);
+                if result_tx
+                    .send((index, puzzle.name().to_string(), report))
+                    .is_err()
+                {
+                    break;
+                }
+            })
+        })
+        .collect();
+    drop(result_tx);
+
+    let mut report = BatchReport {
+        puzzles: 0,
+        workers,
+        elapsed: Duration::ZERO,
+        solved: 0,
+        solve_time: Duration::ZERO,
+    };
+    let mut puzzles = puzzles.fuse();
+    let mut puzzle_tx = Some(puzzle_tx);
+    let mut read_error = None;
+    let mut read = 0;
+    // Finished puzzles waiting for an earlier one before they can be reported
+    let mut finished = BTreeMap::new();
+
+    loop {
+        match &puzzle_tx {
+            Some(sender) if read - report.puzzles < read_ahead => {
+                match puzzles.next() {
+                    Some(Ok(puzzle)) => {
+                        if sender.send((read, puzzle)).await.is_err() {
+                            break;
+                        }
+                        read += 1;
+                    }
+                    Some(Err(e)) => {
+                        read_error = Some(e);
+                        puzzle_tx = None;
+                    }
+                    None => puzzle_tx = None,
+                }
+                continue;
+            }
+            _ => {}
+        }
+
+        let Some((index, name, solve_report)) = result_rx.recv().await else {
+            break;
+        };
+        finished.insert(index, (name, solve_report));
+        while let Some((name, solve_report)) = finished.remove(&report.puzzles) {
+            on_result(&name, &solve_report);
+            report.puzzles += 1;
+            report.solved += solve_report.is_solved() as usize;
+            report.solve_time += solve_report.elapsed;
+        }
+    }
+
+    for task in tasks {
+        task.await
+            .map_err(|e| Error::Generic(format!("Batch worker failed: {}", e)))?;
+    }
+    if let Some(e) = read_error {
+        return Err(e);
+    }
+    report.elapsed = start_time.elapsed();
+    Ok(report)
+}
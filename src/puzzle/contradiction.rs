@@ -0,0 +1,155 @@
+//! Detection of positions that can't lead to a solution
+
+use crate::prelude::*;
+
+use std::fmt;
+
+/// Why a position can't be completed, with the cells involved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Contradiction {
+    /// The same digit is placed more than once in a house.
+    DuplicateDigit {
+        digit: u8,
+        house: House,
+        cells: Vec<(usize, usize)>,
+    },
+    /// An empty cell has no candidates left.
+    NoCandidates { cell: (usize, usize) },
+    /// A digit missing from a house has no empty cell left to go in.
+    NoHome { digit: u8, house: House },
+    /// A killer cage's digits and candidates can't add up to its sum.
+    CageSum { sum: u8, cells: Vec<(usize, usize)> },
+    /// A row or column can't have a sandwich adding up to its clue.
+    SandwichSum { house: House, sum: u8 },
+}
+
+/// How a solve attempt ended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SolveOutcome {
+    Solved,
+    /// The position contradicts itself, either as given or after a logical step.
+    Contradiction(Contradiction),
+    /// Logic found no contradiction but the search backend found no solution either.
+    NoSolution,
+}
+
+impl SolveOutcome {
+    pub fn is_solved(&self) -> bool {
+        *self == SolveOutcome::Solved
+    }
+}
+
+impl fmt::Display for Contradiction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Contradiction::DuplicateDigit {
+                digit,
+                house,
+                cells,
+            } => {
+                let cells: Vec<String> = cells
+                    .iter()
+                    .map(|(row, col)| format!("r{}c{}", row + 1, col + 1))
+                    .collect();
+                write!(
+                    f,
+                    "{} appears more than once in {} at {}",
+                    digit_char(*digit),
+                    house,
+                    cells.join(", ")
+                )
+            }
+            Contradiction::NoCandidates { cell: (row, col) } => {
+                write!(f, "r{}c{} has no candidates left", row + 1, col + 1)
+            }
+            Contradiction::NoHome { digit, house } => {
+                write!(f, "{} has no place left in {}", digit_char(*digit), house)
+            }
+            Contradiction::CageSum { sum, cells } => {
+                let cells: Vec<String> = cells
+                    .iter()
+                    .map(|(row, col)| format!("r{}c{}", row + 1, col + 1))
+                    .collect();
+                write!(f, "the cage at {} can't total {}", cells.join(", "), sum)
+            }
+            Contradiction::SandwichSum { house, sum } => {
+                write!(f, "the sandwich in {} can't total {}", house, sum)
+            }
+        }
+    }
+}
+
+impl fmt::Display for SolveOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SolveOutcome::Solved => f.write_str("solved"),
+            SolveOutcome::Contradiction(contradiction) => {
+                write!(f, "contradiction: {}", contradiction)
+            }
+            SolveOutcome::NoSolution => f.write_str("no solution"),
+        }
+    }
+}
+
+impl SudokuBoard {
+    /// The first contradiction in the current position, checking for duplicate digits,
+    /// then empty cells without candidates, then digits with no home in a house, then
+    /// killer cages and sandwich clues.
+    pub fn find_contradiction(&self) -> Option<Contradiction> {
+        for (index, house) in self.units.houses().enumerate() {
+            let mut seen: Mask = 0;
+            for &(r, c) in house {
+                let digit = self.grid[r][c];
+                if digit == 0 {
+                    continue;
+                }
+                if seen & bit(digit) != 0 {
+                    return Some(Contradiction::DuplicateDigit {
+                        digit,
+                        house: self.units.label(index),
+                        cells: house
+                            .iter()
+                            .copied()
+                            .filter(|&(r, c)| self.grid[r][c] == digit)
+                            .collect(),
+                    });
+                }
+                seen |= bit(digit);
+            }
+        }
+
+        for row in 0..self.size() {
+            for col in 0..self.size() {
+                if self.grid[row][col] == 0 && self.candidates[row][col] == 0 {
+                    return Some(Contradiction::NoCandidates { cell: (row, col) });
+                }
+            }
+        }
+
+        for (index, house) in self.units.houses().enumerate() {
+            let homes = house
+                .iter()
+                .filter(|&&(r, c)| self.grid[r][c] == 0)
+                .fold(self.house_masks[index], |mask, &(r, c)| {
+                    mask | self.candidates[r][c]
+                });
+            if let Some(digit) = digits(self.units.shape().all_digits() & !homes).next() {
+                return Some(Contradiction::NoHome {
+                    digit,
+                    house: self.units.label(index),
+                });
+            }
+        }
+
+        self.find_cage_contradiction()
+            .or_else(|| self.find_sandwich_contradiction())
+    }
+
+    /// Checks the current position, returning `Error::Contradiction` if it can't be completed.
+    pub fn validate(&self) -> Result<()> {
+        match self.find_contradiction() {
+            Some(contradiction) => Err(Error::Contradiction(contradiction)),
+            None => Ok(()),
+        }
+    }
+}
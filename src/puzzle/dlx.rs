@@ -0,0 +1,322 @@
+//! Dancing Links (Algorithm X) exact cover solver
+
+use crate::prelude::*;
+
+/// Exact cover matrix stored as Knuth's dancing links.
+///
+/// Primary columns must be covered exactly once; secondary columns at most once.
+/// Node 0 is the root, nodes `1..=columns` are the column headers, and the remaining
+/// nodes are the 1s of the matrix.
+pub struct Dlx {
+    left: Vec<usize>,
+    right: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+    column: Vec<usize>,
+    row: Vec<usize>,
+    size: Vec<usize>,
+    rows: usize,
+    starts: Vec<usize>,   // First node of each row
+    selected: Vec<usize>, // Nodes of the rows chosen by `select`, part of every cover
+    blocked: bool,        // A selected row clashed with an earlier one, so nothing covers
+    guesses: usize,       // Rows tried during search
+    backtracks: usize,    // Rows undone without leading to a solution
+}
+
+impl Dlx {
+    pub fn new(primary: usize, secondary: usize) -> Dlx {
+        let columns = primary + secondary;
+        let mut dlx = Dlx {
+            left: Vec::with_capacity(columns + 1),
+            right: Vec::with_capacity(columns + 1),
+            up: Vec::with_capacity(columns + 1),
+            down: Vec::with_capacity(columns + 1),
+            column: Vec::with_capacity(columns + 1),
+            row: Vec::with_capacity(columns + 1),
+            size: vec![0; columns + 1],
+            rows: 0,
+            starts: Vec::new(),
+            selected: Vec::new(),
+            blocked: false,
+            guesses: 0,
+            backtracks: 0,
+        };
+
+        for node in 0..=columns {
+            // Only the root and primary headers are linked into the header list;
+            // secondary headers link to themselves so they are never chosen.
+            let (left, right) = match node {
+                0 => (primary, if primary == 0 { 0 } else { 1 }),
+                n if n <= primary => (n - 1, if n == primary { 0 } else { n + 1 }),
+                n => (n, n),
+            };
+            dlx.left.push(left);
+            dlx.right.push(right);
+            dlx.up.push(node);
+            dlx.down.push(node);
+            dlx.column.push(node);
+            dlx.row.push(usize::MAX);
+        }
+
+        dlx
+    }
+
+    /// Adds a row covering the given (zero-based) columns and returns its id.
+    pub fn add_row(&mut self, columns: &[usize]) -> usize {
+        let row = self.rows;
+        self.rows += 1;
+
+        let first = self.left.len();
+        self.starts.push(first);
+        for (i, &col) in columns.iter().enumerate() {
+            let header = col + 1;
+            let node = first + i;
+
+            self.left.push(if i == 0 {
+                first + columns.len() - 1
+            } else {
+                node - 1
+            });
+            self.right.push(if i + 1 == columns.len() {
+                first
+            } else {
+                node + 1
+            });
+
+            // Insert at the bottom of the column
+            self.up.push(self.up[header]);
+            self.down.push(header);
+            let last = self.up[header];
+            self.down[last] = node;
+            self.up[header] = node;
+
+            self.column.push(header);
+            self.row.push(row);
+            self.size[header] += 1;
+        }
+
+        row
+    }
+
+    /// Puts `row` in every cover before the search starts, covering its columns so the
+    /// search never counts it as tried. If a column is already covered by an earlier
+    /// selected row, the matrix is left with no cover.
+    pub fn select(&mut self, row: usize) {
+        let first = self.starts[row];
+        let mut node = first;
+        loop {
+            let header = self.column[node];
+            if self.right[self.left[header]] != header {
+                self.blocked = true;
+                return;
+            }
+            node = self.right[node];
+            if node == first {
+                break;
+            }
+        }
+        loop {
+            self.cover(self.column[node]);
+            node = self.right[node];
+            if node == first {
+                break;
+            }
+        }
+        self.selected.push(first);
+    }
+
+    /// Finds up to `limit` exact covers, each as the list of row ids it selects.
+    pub fn solve(&mut self, limit: usize) -> Vec<Vec<usize>> {
+        let mut solutions = Vec::new();
+        let mut partial = self.selected.clone();
+        if limit > 0 && !self.blocked {
+            self.search(&mut partial, &mut solutions, limit);
+        }
+        solutions
+    }
+
+    /// Rows tried by the searches so far.
+    pub fn guesses(&self) -> usize {
+        self.guesses
+    }
+
+    /// Rows undone by the searches so far because they led to no solution.
+    pub fn backtracks(&self) -> usize {
+        self.backtracks
+    }
+
+    fn search(&mut self, partial: &mut Vec<usize>, solutions: &mut Vec<Vec<usize>>, limit: usize) {
+        if self.right[0] == 0 {
+            solutions.push(partial.iter().map(|&node| self.row[node]).collect());
+            return;
+        }
+
+        // Branch on the primary column with the fewest remaining rows
+        let mut header = self.right[0];
+        let mut col = header;
+        while col != 0 {
+            if self.size[col] < self.size[header] {
+                header = col;
+            }
+            col = self.right[col];
+        }
+        if self.size[header] == 0 {
+            return;
+        }
+
+        self.cover(header);
+        let mut node = self.down[header];
+        while node != header {
+            partial.push(node);
+            self.guesses += 1;
+            let found = solutions.len();
+            let mut j = self.right[node];
+            while j != node {
+                self.cover(self.column[j]);
+                j = self.right[j];
+            }
+
+            self.search(partial, solutions, limit);
+
+            let mut j = self.left[node];
+            while j != node {
+                self.uncover(self.column[j]);
+                j = self.left[j];
+            }
+            partial.pop();
+            if solutions.len() == found {
+                self.backtracks += 1;
+            }
+
+            if solutions.len() >= limit {
+                break;
+            }
+            node = self.down[node];
+        }
+        self.uncover(header);
+    }
+
+    fn cover(&mut self, header: usize) {
+        self.right[self.left[header]] = self.right[header];
+        self.left[self.right[header]] = self.left[header];
+
+        let mut i = self.down[header];
+        while i != header {
+            let mut j = self.right[i];
+            while j != i {
+                self.down[self.up[j]] = self.down[j];
+                self.up[self.down[j]] = self.up[j];
+                self.size[self.column[j]] -= 1;
+                j = self.right[j];
+            }
+            i = self.down[i];
+        }
+    }
+
+    fn uncover(&mut self, header: usize) {
+        let mut i = self.up[header];
+        while i != header {
+            let mut j = self.left[i];
+            while j != i {
+                self.size[self.column[j]] += 1;
+                self.down[self.up[j]] = j;
+                self.up[self.down[j]] = j;
+                j = self.left[j];
+            }
+            i = self.up[i];
+        }
+
+        self.right[self.left[header]] = header;
+        self.left[self.right[header]] = header;
+    }
+}
+
+impl SudokuBoard {
+    /// Builds the exact cover matrix for the board: one primary column per cell and one per
+    /// (house, digit), and one row per possible placement. Filled cells only get the row for
+    /// their value, selected up front so only empty cells are searched, and conflicting
+    /// givens leave the matrix without a cover.
+    fn exact_cover(&self) -> (Dlx, Vec<(usize, usize, u8)>) {
+        let size = self.size();
+        let cells = size * size;
+        let houses: Vec<&Vec<(usize, usize)>> = self.units.houses().collect();
+        let mut dlx = Dlx::new(cells + houses.len() * size, 0);
+        let mut placements = Vec::new();
+        let mut filled = Vec::new();
+
+        for row in 0..size {
+            for col in 0..size {
+                let options = match self.grid[row][col] {
+                    0 => self.units.shape().all_digits() & !self.placed_digits(row, col),
+                    value => bit(value),
+                };
+
+                for num in digits(options) {
+                    let mut columns = vec![row * size + col];
+                    for &house in self.units.cell_houses(row, col) {
+                        columns.push(cells + house * size + num as usize - 1);
+                    }
+                    let id = dlx.add_row(&columns);
+                    placements.push((row, col, num));
+                    if self.grid[row][col] != 0 {
+                        filled.push(id);
+                    }
+                }
+            }
+        }
+
+        for row in filled {
+            dlx.select(row);
+        }
+        (dlx, placements)
+    }
+
+    /// Finds up to `limit` complete grids that extend the current one.
+    pub fn dlx_solutions(&self, limit: usize) -> Vec<Grid> {
+        self.dlx_search(limit).0
+    }
+
+    /// Runs the exact cover search, returning the grids found and the finished matrix,
+    /// which carries the search counts.
+    fn dlx_search(&self, limit: usize) -> (Vec<Grid>, Dlx) {
+        let (mut dlx, placements) = self.exact_cover();
+
+        let grids = dlx
+            .solve(limit)
+            .into_iter()
+            .map(|rows| {
+                let mut grid = self.grid.clone();
+                for row in rows {
+                    let (r, c, num) = placements[row];
+                    grid[r][c] = num;
+                }
+                grid
+            })
+            .collect();
+        (grids, dlx)
+    }
+
+    /// Exact cover search: Fills the grid with the first solution found by Dancing Links.
+    /// Sums can't be expressed as exact cover, so boards with sum rules are backtracked.
+    pub fn solve_dlx(&mut self) -> bool {
+        if self.has_sum_rules() {
+            return self.brute_force();
+        }
+        let (mut grids, dlx) = self.dlx_search(1);
+        self.guesses += dlx.guesses();
+        self.backtracks += dlx.backtracks();
+        let Some(grid) = grids.pop() else {
+            return false;
+        };
+
+        for (row, values) in grid.iter().enumerate() {
+            for (col, &value) in values.iter().enumerate() {
+                if self.grid[row][col] == 0 {
+                    self.place(row, col, value);
+                    self.candidates[row][col] = 0;
+                }
+            }
+        }
+        true
+    }
+}
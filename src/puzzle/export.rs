@@ -0,0 +1,82 @@
+//! Writing puzzles back out in the formats `import` reads
+
+use crate::prelude::*;
+
+use std::io::Write;
+
+impl Puzzle {
+    /// The puzzle as a header line followed by its rows of digits, 0 for blanks, then the
+    /// cage map of a killer puzzle, the clue lines of a sandwich puzzle or the region map of a
+    /// jigsaw puzzle.
+    pub fn to_grid_text(&self) -> String {
+        let mut text = format!("{}\n", self.header());
+        for row in &self.board.grid {
+            for &cell in row {
+                text.push(match cell {
+                    0 => '0',
+                    n => digit_char(n),
+                });
+            }
+            text.push('\n');
+        }
+        if !self.cages().is_empty() {
+            for line in cage_map(self.cages()) {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+        if !self.sandwiches().is_empty() {
+            text.push_str(&format!("{}\n", self.sandwiches()));
+        }
+        if let Some(regions) = self.regions() {
+            for line in regions.to_lines() {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        }
+        text
+    }
+
+    /// The puzzle on one line: all its cells with `.` for blanks, then its header as a comment.
+    pub fn to_line(&self) -> String {
+        format!("{} # {}", self.cells(), self.header())
+    }
+
+    /// The cells in reading order, with `.` for blanks.
+    pub fn cells(&self) -> String {
+        self.board
+            .grid
+            .iter()
+            .flatten()
+            .map(|&cell| match cell {
+                0 => '.',
+                n => digit_char(n),
+            })
+            .collect()
+    }
+}
+
+/// Writes puzzles in the header and rows layout as they are read, stopping at the first
+/// error.
+pub fn write_grid_puzzles(
+    writer: &mut dyn Write,
+    puzzles: impl IntoIterator<Item = Result<Puzzle>>,
+) -> Result<()> {
+    for puzzle in puzzles {
+        let puzzle = puzzle?;
+        write!(writer, "{}", puzzle.to_grid_text()).map_err(Error::IO)?;
+    }
+    Ok(())
+}
+
+/// Writes puzzles one per line as they are read, stopping at the first error.
+pub fn write_line_puzzles(
+    writer: &mut dyn Write,
+    puzzles: impl IntoIterator<Item = Result<Puzzle>>,
+) -> Result<()> {
+    for puzzle in puzzles {
+        let puzzle = puzzle?;
+        writeln!(writer, "{}", puzzle.to_line()).map_err(Error::IO)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,586 @@
+//! Puzzle file formats and the registry used to pick one
+
+use crate::prelude::*;
+
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Read, Write};
+
+/// Puzzles read lazily from a source, one `Result` per puzzle.
+pub type PuzzleStream = Box<dyn Iterator<Item = Result<Puzzle>>>;
+
+/// A way of storing puzzles as text.
+pub trait PuzzleFormat: Send + Sync {
+    /// Unique name, as accepted by `--format`.
+    fn name(&self) -> &'static str;
+
+    /// Whether `sample`, the start of the input, looks like this format.
+    fn detect(&self, sample: &str) -> bool;
+
+    /// Streams the puzzles in `reader`, reporting errors against `context`.
+    fn read(&self, reader: Box<dyn BufRead>, context: ParseContext) -> PuzzleStream;
+
+    /// Writes each puzzle as it comes from `puzzles`, stopping at the first error, so only
+    /// the puzzle being written is held in memory.
+    fn write(&self, writer: &mut dyn Write, puzzles: PuzzleStream) -> Result<()>;
+}
+
+/// The known formats, in the order auto-detection tries them.
+pub struct FormatRegistry {
+    formats: Vec<Box<dyn PuzzleFormat>>,
+}
+
+impl Default for FormatRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FormatRegistry {
+    /// A registry with every built-in format.
+    pub fn new() -> FormatRegistry {
+        let mut registry = FormatRegistry {
+            formats: Vec::new(),
+        };
+        registry.register(JsonFormat);
+        registry.register(SdkFormat);
+        registry.register(LineFormat);
+        registry.register(GridFormat);
+        registry
+    }
+
+    /// Adds a format, tried by auto-detection after the ones already registered.
+    pub fn register(&mut self, format: impl PuzzleFormat + 'static) {
+        self.formats.push(Box::new(format));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.formats.iter().map(|format| format.name())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&dyn PuzzleFormat> {
+        self.formats
+            .iter()
+            .map(|format| format.as_ref())
+            .find(|format| format.name() == name)
+            .ok_or_else(|| Error::UnknownFormat {
+                name: name.to_string(),
+                known: self.names().collect::<Vec<_>>().join(", "),
+            })
+    }
+
+    /// The first format whose detection accepts `sample`.
+    pub fn detect(&self, sample: &str) -> Option<&dyn PuzzleFormat> {
+        self.formats
+            .iter()
+            .map(|format| format.as_ref())
+            .find(|format| format.detect(sample))
+    }
+
+    /// Streams the puzzles in a reader using the named format. With "auto" the format is
+    /// detected from the reader's buffered start, falling back to the grid format.
+    pub fn read(
+        &self,
+        mut reader: Box<dyn BufRead>,
+        format: &str,
+        context: ParseContext,
+    ) -> Result<PuzzleStream> {
+        let format = match format {
+            "auto" => {
+                let sample = String::from_utf8_lossy(reader.fill_buf().map_err(Error::IO)?);
+                match self.detect(&sample) {
+                    Some(format) => format,
+                    None => self.get("grid")?,
+                }
+            }
+            name => self.get(name)?,
+        };
+        Ok(format.read(reader, context))
+    }
+}
+
+/// The first line of `sample` that isn't blank or a `#` comment.
+fn first_line(sample: &str) -> Option<&str> {
+    sample
+        .lines()
+        .map(str::trim_end)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Fails for a puzzle with cages, sandwich clues or jigsaw regions, which `format` has no way
+/// of storing.
+fn check_no_constraints(format: &dyn PuzzleFormat, puzzle: &Puzzle) -> Result<()> {
+    let constraints = if !puzzle.cages().is_empty() {
+        "cages"
+    } else if !puzzle.sandwiches().is_empty() {
+        "sandwich clues"
+    } else if puzzle.regions().is_some() {
+        "regions"
+    } else {
+        return Ok(());
+    };
+    Err(Error::Generic(format!(
+        "The {} format can't store the {} of {}",
+        format.name(),
+        constraints,
+        puzzle.name()
+    )))
+}
+
+/// A header line naming the puzzle followed by the rows of digits, 0 for blanks, then a cage
+/// map for killer puzzles, clue lines for sandwich puzzles or a region map for jigsaw puzzles.
+pub struct GridFormat;
+
+impl PuzzleFormat for GridFormat {
+    fn name(&self) -> &'static str {
+        "grid"
+    }
+
+    fn detect(&self, sample: &str) -> bool {
+        first_line(sample).is_some_and(|line| line.chars().any(|c| c.is_alphabetic()))
+    }
+
+    fn read(&self, reader: Box<dyn BufRead>, context: ParseContext) -> PuzzleStream {
+        Box::new(PuzzleReader::new(reader, context))
+    }
+
+    fn write(&self, writer: &mut dyn Write, puzzles: PuzzleStream) -> Result<()> {
+        write_grid_puzzles(writer, puzzles)
+    }
+}
+
+/// One puzzle per line: all its cells, e.g. 81 characters, with `0` or `.` for blanks and an
+/// optional comment.
+pub struct LineFormat;
+
+impl PuzzleFormat for LineFormat {
+    fn name(&self) -> &'static str {
+        "line"
+    }
+
+    fn detect(&self, sample: &str) -> bool {
+        first_line(sample).is_some_and(is_line_puzzle)
+    }
+
+    fn read(&self, reader: Box<dyn BufRead>, context: ParseContext) -> PuzzleStream {
+        Box::new(PuzzleReader::new(reader, context))
+    }
+
+    fn write(&self, writer: &mut dyn Write, puzzles: PuzzleStream) -> Result<()> {
+        let puzzles = puzzles.map(|puzzle| {
+            let puzzle = puzzle?;
+            check_no_constraints(self, &puzzle)?;
+            Ok(puzzle)
+        });
+        write_line_puzzles(writer, puzzles)
+    }
+}
+
+/// SadMan Sudoku: `#` metadata lines followed by 9 rows with `.` for blanks, so only 9x9. `#D` holds the
+/// header, `#A` the author, `#S` the source and `#L` the difficulty, and `#C` a comment, which
+/// is skipped. Several puzzles may follow one another.
+pub struct SdkFormat;
+
+/// The SadMan tags `SdkFormat` knows, the letter following the `#`.
+const SDK_TAGS: [char; 5] = ['D', 'A', 'S', 'L', 'C'];
+
+impl PuzzleFormat for SdkFormat {
+    fn name(&self) -> &'static str {
+        "sdk"
+    }
+
+    fn detect(&self, sample: &str) -> bool {
+        // Other collections open with `#` comments too, so a tag only counts before a 9x9 grid
+        let is_row = |line: &str| line.len() == 9 && line.chars().all(is_cell_char);
+        let tagged = sample
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .and_then(|line| line.strip_prefix('#')?.chars().next())
+            .is_some_and(|tag| SDK_TAGS.contains(&tag));
+        first_line(sample)
+            .is_some_and(|line| (tagged && is_row(line)) || (is_row(line) && line.contains('.')))
+    }
+
+    fn read(&self, reader: Box<dyn BufRead>, context: ParseContext) -> PuzzleStream {
+        let mut lines = reader.lines();
+        let mut line_number = 0;
+        let mut count = 0;
+        Box::new(std::iter::from_fn(move || {
+            let mut metadata: Option<Metadata> = None;
+            let mut grid: Grid = Vec::with_capacity(9);
+            while grid.len() < 9 {
+                let line = match lines.next() {
+                    Some(Ok(line)) => line,
+                    Some(Err(e)) => return Some(Err(Error::IO(e))),
+                    None if grid.is_empty() => return None,
+                    None => {
+                        return Some(Err(ParseError::MissingRows {
+                            at: context.at(line_number + 1, 1),
+                            puzzle: metadata.map_or_else(
+                                || format!("Puzzle {}", count + 1),
+                                |metadata| metadata.name,
+                            ),
+                            found: grid.len(),
+                            expected: 9,
+                        }
+                        .into()))
+                    }
+                };
+                line_number += 1;
+
+                if let Some((tag, value)) =
+                    line.strip_prefix('#').and_then(|l| l.split_at_checked(1))
+                {
+                    let value = value.trim();
+                    let entry = metadata.get_or_insert_with(Metadata::default);
+                    match tag {
+                        "D" => {
+                            let column =
+                                column_at(&line, line.len() - line[2..].trim_start().len());
+                            let header = match Metadata::parse(value, &context, line_number, column)
+                            {
+                                Ok(header) => header,
+                                Err(e) => return Some(Err(e)),
+                            };
+                            entry.name = header.name;
+                            entry.variation = header.variation.or(entry.variation);
+                            entry.difficulty = header.difficulty.or(entry.difficulty);
+                            entry.author = header.author.or(entry.author.take());
+                            entry.source = header.source.or(entry.source.take());
+                            entry.boxes = header.boxes.or(entry.boxes);
+                        }
+                        "A" => entry.author = Some(value.to_string()),
+                        "S" => entry.source = Some(value.to_string()),
+                        "L" => entry.difficulty = value.parse().ok().or(entry.difficulty),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if line.trim().is_empty() || line.starts_with('#') {
+                    continue;
+                }
+
+                let row = line.trim_end();
+                let length = row.chars().count();
+                if length != 9 {
+                    return Some(Err(ParseError::RowLength {
+                        at: context.at(line_number, 1),
+                        puzzle: metadata.map_or_else(
+                            || format!("Puzzle {}", count + 1),
+                            |metadata| metadata.name,
+                        ),
+                        row: grid.len() + 1,
+                        found: length,
+                        expected: 9,
+                        text: line,
+                    }
+                    .into()));
+                }
+                match context.cells(row, 9, line_number, 1) {
+                    Ok(cells) => grid.push(cells),
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
+            count += 1;
+            let mut metadata = metadata.unwrap_or_default();
+            if metadata.name.is_empty() {
+                metadata.name = format!("Puzzle {}", count);
+            }
+            let constraints = match context.shape(&metadata, 9, line_number, 1) {
+                Ok(shape) => Constraints {
+                    shape,
+                    ..Constraints::default()
+                },
+                Err(e) => return Some(Err(e)),
+            };
+            if let Err(e) = context.check_variation(&metadata, &constraints, line_number, 1) {
+                return Some(Err(e));
+            }
+            Some(Ok(Puzzle::from_metadata(metadata, grid, constraints)))
+        }))
+    }
+
+    fn write(&self, writer: &mut dyn Write, puzzles: PuzzleStream) -> Result<()> {
+        for puzzle in puzzles {
+            let puzzle = puzzle?;
+            check_no_constraints(self, &puzzle)?;
+            if puzzle.shape().size() != 9 {
+                return Err(Error::Generic(format!(
+                    "The sdk format can't store the {0}x{0} grid of {1}",
+                    puzzle.shape().size(),
+                    puzzle.name()
+                )));
+            }
+            let cells = puzzle.cells();
+            let metadata = puzzle.metadata();
+            let header = Metadata {
+                name: metadata.name,
+                variation: metadata.variation,
+                boxes: metadata.boxes,
+                ..Metadata::default()
+            };
+            writeln!(writer, "#D{}", header).map_err(Error::IO)?;
+            if let Some(author) = &metadata.author {
+                writeln!(writer, "#A{}", author).map_err(Error::IO)?;
+            }
+            if let Some(source) = &metadata.source {
+                writeln!(writer, "#S{}", source).map_err(Error::IO)?;
+            }
+            if let Some(difficulty) = metadata.difficulty {
+                writeln!(writer, "#L{}", difficulty).map_err(Error::IO)?;
+            }
+            for row in 0..9 {
+                writeln!(writer, "{}", &cells[row * 9..row * 9 + 9]).map_err(Error::IO)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A JSON array of `{"name": ..., "puzzle": ...}` objects, the puzzle as all its cells, e.g.
+/// 81 characters, with optional `difficulty`, `variation`, `author`, `source` and `boxes`
+/// fields. Killer puzzles add
+/// `cages`, each `{"sum": 15, "cells": ["r1c1", "r1c2"]}`, and sandwich puzzles add
+/// `sandwiches`, `{"rows": [...], "columns": [...]}` with 9 clues each, `null` for none.
+/// Jigsaw puzzles add `regions`, a character for each cell naming its region in reading order.
+pub struct JsonFormat;
+
+#[derive(Serialize, Deserialize)]
+struct JsonCage {
+    sum: u8,
+    cells: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonSandwiches {
+    rows: [Option<u8>; 9],
+    columns: [Option<u8>; 9],
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonPuzzle {
+    name: String,
+    puzzle: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    difficulty: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    variation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    boxes: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    cages: Vec<JsonCage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sandwiches: Option<JsonSandwiches>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    regions: Option<String>,
+}
+
+impl PuzzleFormat for JsonFormat {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn detect(&self, sample: &str) -> bool {
+        sample.trim_start().starts_with('[')
+    }
+
+    fn read(&self, mut reader: Box<dyn BufRead>, context: ParseContext) -> PuzzleStream {
+        // JSON can't be split into puzzles before it is parsed, so the whole array is read
+        let mut text = String::new();
+        if let Err(e) = reader.read_to_string(&mut text) {
+            return Box::new(std::iter::once(Err(Error::IO(e))));
+        }
+        let entries: Vec<JsonPuzzle> = match serde_json::from_str(&text) {
+            Ok(entries) => entries,
+            Err(e) => {
+                return Box::new(std::iter::once(Err(ParseError::Json {
+                    // serde counts columns in bytes
+                    at: context.at(
+                        e.line(),
+                        text.lines()
+                            .nth(e.line().saturating_sub(1))
+                            .map_or(e.column(), |line| {
+                                column_at(line, e.column().saturating_sub(1))
+                            }),
+                    ),
+                    message: e.to_string(),
+                }
+                .into())));
+            }
+        };
+
+        // Locate each puzzle string in the text so cell errors point at it
+        let mut offset = 0;
+        let puzzles: Vec<Result<Puzzle>> = entries
+            .into_iter()
+            .map(|entry| {
+                let quoted = format!("\"{}\"", entry.puzzle);
+                offset += text[offset..].find(&quoted).unwrap_or(0);
+                let before = &text[..offset];
+                let line = before.matches('\n').count() + 1;
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                let column = column_at(&text[line_start..], offset - line_start) + 1;
+
+                let grid = context.grid(&entry.puzzle, line, column)?;
+                let tag = |text: String| -> Error {
+                    ParseError::UnknownTag {
+                        at: context.at(line, column),
+                        text,
+                    }
+                    .into()
+                };
+                let metadata = Metadata {
+                    name: entry.name,
+                    difficulty: entry
+                        .difficulty
+                        .map(|text| text.parse().map_err(|_| tag(text)))
+                        .transpose()?,
+                    variation: entry
+                        .variation
+                        .map(|text| text.parse().map_err(|_| tag(text)))
+                        .transpose()?,
+                    author: entry.author,
+                    source: entry.source,
+                    boxes: entry
+                        .boxes
+                        .map(|text| text.parse().map_err(|_| tag(text)))
+                        .transpose()?,
+                };
+                let size = grid.len();
+                let shape = context.shape(&metadata, size, line, column)?;
+                let cages = entry
+                    .cages
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, cage)| {
+                        let cells = cage
+                            .cells
+                            .iter()
+                            .map(|cell| {
+                                parse_cell(cell).ok_or_else(|| -> Error {
+                                    ParseError::InvalidCage {
+                                        at: context.at(line, column),
+                                        cage: (index + 1).to_string(),
+                                        reason: format!("has an invalid cell '{}'", cell),
+                                    }
+                                    .into()
+                                })
+                            })
+                            .collect::<Result<_>>()?;
+                        Ok(Cage::new(cage.sum, cells))
+                    })
+                    .collect::<Result<Vec<Cage>>>()?;
+                Cage::check_all(&cages, &context, line, column)?;
+
+                let sandwiches =
+                    entry
+                        .sandwiches
+                        .map_or_else(Sandwiches::default, |clues| Sandwiches {
+                            rows: clues.rows,
+                            cols: clues.columns,
+                        });
+                for (_, sum) in sandwiches.clues() {
+                    Sandwiches::check_clue(sum).map_err(|reason| ParseError::InvalidClue {
+                        at: context.at(line, column),
+                        text: sum.to_string(),
+                        reason,
+                    })?;
+                }
+
+                let regions = entry
+                    .regions
+                    .map(|text| -> Result<Regions> {
+                        let ids: Vec<char> = text.chars().collect();
+                        if ids.len() != size * size {
+                            return Err(ParseError::CellCount {
+                                at: context.at(line, column),
+                                found: ids.len(),
+                                text,
+                            }
+                            .into());
+                        }
+                        let map: Vec<Vec<char>> = ids.chunks(size).map(<[char]>::to_vec).collect();
+                        Regions::from_ids(&map).map_err(|(id, reason)| {
+                            ParseError::InvalidRegion {
+                                at: context.at(line, column),
+                                region: id.to_string(),
+                                reason,
+                            }
+                            .into()
+                        })
+                    })
+                    .transpose()?;
+
+                let constraints = Constraints {
+                    shape,
+                    cages,
+                    sandwiches,
+                    regions,
+                    ..Constraints::default()
+                };
+                let variation = constraints.variation();
+                if size != 9 && matches!(variation, Variation::Killer | Variation::Sandwhich) {
+                    return Err(ParseError::UnsupportedSize {
+                        at: context.at(line, column),
+                        puzzle: metadata.name,
+                        variation: variation.to_string(),
+                        size,
+                    }
+                    .into());
+                }
+                context.check_variation(&metadata, &constraints, line, column)?;
+                Ok(Puzzle::from_metadata(metadata, grid, constraints))
+            })
+            .collect();
+        Box::new(puzzles.into_iter())
+    }
+
+    fn write(&self, writer: &mut dyn Write, puzzles: PuzzleStream) -> Result<()> {
+        // Written an entry at a time, laid out as a pretty-printed array would be
+        write!(writer, "[").map_err(Error::IO)?;
+        let mut empty = true;
+        for puzzle in puzzles {
+            let entry = serde_json::to_string_pretty(&JsonPuzzle::from(&puzzle?))?;
+            let separator = if empty { "\n" } else { ",\n" };
+            write!(writer, "{}  {}", separator, entry.replace('\n', "\n  ")).map_err(Error::IO)?;
+            empty = false;
+        }
+        writeln!(writer, "{}]", if empty { "" } else { "\n" }).map_err(Error::IO)
+    }
+}
+
+impl From<&Puzzle> for JsonPuzzle {
+    fn from(puzzle: &Puzzle) -> JsonPuzzle {
+        let metadata = puzzle.metadata();
+        JsonPuzzle {
+            name: metadata.name,
+            puzzle: puzzle.cells(),
+            difficulty: metadata.difficulty.map(|d| d.to_string()),
+            variation: metadata.variation.map(|v| v.to_string()),
+            author: metadata.author,
+            source: metadata.source,
+            boxes: metadata.boxes.map(|shape| shape.to_string()),
+            cages: puzzle
+                .cages()
+                .iter()
+                .map(|cage| JsonCage {
+                    sum: cage.sum,
+                    cells: cage
+                        .cells
+                        .iter()
+                        .map(|(row, col)| format!("r{}c{}", row + 1, col + 1))
+                        .collect(),
+                })
+                .collect(),
+            sandwiches: (!puzzle.sandwiches().is_empty()).then(|| JsonSandwiches {
+                rows: puzzle.sandwiches().rows,
+                columns: puzzle.sandwiches().cols,
+            }),
+            regions: puzzle.regions().map(|regions| regions.to_lines().concat()),
+        }
+    }
+}
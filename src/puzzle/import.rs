@@ -1,58 +1,160 @@
 use crate::prelude::*;
 
-use std::collections::HashMap;
-use std::fs;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
 
-pub fn read_sudoku_puzzles(filename: &str) -> Result<Vec<Puzzle>> {
-    let content = fs::read_to_string(filename).map_err(|e| Error::IO(e))?;
-
-    let mut puzzles = Vec::new();
-    let mut lines = content.lines();
-
-    while let Some(line) = lines.next() {
-        if line.chars().any(|c| c.is_alphabetic()) {
-            let mut grid = [[0u8; 9]; 9];
-            let name = line.to_string();
-
-            for i in 0..9 {
-                if let Some(row) = lines.next() {
-                    let row_digits: Vec<u8> = row
-                        .chars()
-                        .map(|c| c.to_digit(10).unwrap_or(0) as u8)
-                        .collect();
-
-                    if row_digits.len() != 9 {
-                        return Err(Error::Generic(format!(
-                            "Row {} in puzzle {} doesn't have exactly 9 digits",
-                            i + 1,
-                            name
-                        )));
-                    }
+/// How to read a puzzle source.
+#[derive(Clone, Debug)]
+pub struct ReadOptions {
+    /// Registered format name, or "auto" to detect it.
+    pub format: String,
+    /// Reject characters that aren't digits or `.` instead of reading them as blanks.
+    pub strict: bool,
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            format: "auto".to_string(),
+            strict: false,
+        }
+    }
+}
+
+/// What a format reader needs to know to report errors and apply strict mode.
+#[derive(Clone, Debug)]
+pub struct ParseContext {
+    /// Shown in errors; "<stdin>" when reading standard input.
+    pub path: String,
+    pub strict: bool,
+}
+
+impl ParseContext {
+    pub fn at(&self, line: usize, column: usize) -> Location {
+        Location {
+            path: self.path.clone(),
+            line,
+            column,
+        }
+    }
 
-                    grid[i].copy_from_slice(&row_digits);
-                } else {
-                    return Err(Error::Generic(format!(
-                        "Not enough rows for puzzle {}",
-                        name
-                    )));
+    /// Reads cells as digits from 1 to `size`, written as by `digit_char`, with `0` or `.` for
+    /// blanks. Other characters are blanks too, unless in strict mode, but a digit too large
+    /// for the board always fails. `column` is where `text` starts on its line.
+    pub fn cells(&self, text: &str, size: usize, line: usize, column: usize) -> Result<Vec<u8>> {
+        text.chars()
+            .enumerate()
+            .map(|(i, c)| match digit_value(c) {
+                Some(digit) if digit as usize <= size => Ok(digit),
+                Some(_) => Err(ParseError::DigitOutOfRange {
+                    at: self.at(line, column + i),
+                    found: c,
+                    size,
+                    text: text.to_string(),
+                }
+                .into()),
+                None if c == '0' || c == '.' || !self.strict => Ok(0),
+                _ => Err(ParseError::UnknownCharacter {
+                    at: self.at(line, column + i),
+                    found: c,
+                    text: text.to_string(),
                 }
+                .into()),
+            })
+            .collect()
+    }
+
+    /// Reads a whole grid in reading order, its size following from the number of cells.
+    pub fn grid(&self, text: &str, line: usize, column: usize) -> Result<Grid> {
+        let found = text.chars().count();
+        let Some(size) = Shape::SIZES.into_iter().find(|size| size * size == found) else {
+            return Err(ParseError::CellCount {
+                at: self.at(line, column),
+                found,
+                text: text.to_string(),
             }
+            .into());
+        };
 
-            // Create a SudokuBoard from the grid
-            let board = SudokuBoard::from(grid);
+        let cells = self.cells(text, size, line, column)?;
+        Ok(cells.chunks(size).map(<[u8]>::to_vec).collect())
+    }
 
-            // Create a Puzzle with default variation and difficulty
-            // These could be inferred or parsed from the name if needed
-            let puzzle = Puzzle {
-                name,
-                variation: Variation::Standard, // Default
-                difficulty: Difficulty::Medium, // Default
-                board,
-            };
+    /// The boxes of a grid `size` cells across: those the header gives, or else the usual
+    /// ones for the size. Fails for variations that only exist on a 9x9 board.
+    pub fn shape(
+        &self,
+        metadata: &Metadata,
+        size: usize,
+        line: usize,
+        column: usize,
+    ) -> Result<Shape> {
+        let shape = match metadata.boxes {
+            Some(shape) => shape,
+            None => Shape::for_size(size).unwrap_or_default(),
+        };
+        if shape.size() != size {
+            return Err(ParseError::BoxShape {
+                at: self.at(line, column),
+                puzzle: metadata.name.clone(),
+                shape: shape.to_string(),
+                size,
+            }
+            .into());
+        }
+
+        match metadata.variation {
+            Some(variation @ (Variation::Killer | Variation::Sandwhich)) if size != 9 => {
+                Err(ParseError::UnsupportedSize {
+                    at: self.at(line, column),
+                    puzzle: metadata.name.clone(),
+                    variation: variation.to_string(),
+                    size,
+                }
+                .into())
+            }
+            _ => Ok(shape),
+        }
+    }
 
-            puzzles.push(puzzle);
+    /// Fails for a puzzle whose header tags it as a variation without the constraints that
+    /// make one, e.g. `[killer]` on a format with nowhere to store cages.
+    pub fn check_variation(
+        &self,
+        metadata: &Metadata,
+        constraints: &Constraints,
+        line: usize,
+        column: usize,
+    ) -> Result<()> {
+        let (variation, missing) = match metadata.variation {
+            Some(variation @ Variation::Killer) if constraints.cages.is_empty() => {
+                (variation, "cages")
+            }
+            Some(variation @ Variation::Sandwhich) if constraints.sandwiches.is_empty() => {
+                (variation, "sandwich clues")
+            }
+            Some(variation @ Variation::Jigsaw) if constraints.regions.is_none() => {
+                (variation, "regions")
+            }
+            _ => return Ok(()),
+        };
+        Err(ParseError::MissingConstraints {
+            at: self.at(line, column),
+            puzzle: metadata.name.clone(),
+            variation: variation.to_string(),
+            missing: missing.to_string(),
         }
+        .into())
     }
+}
+
+pub fn read_sudoku_puzzles(filename: &str) -> Result<Vec<Puzzle>> {
+    read_puzzles(filename, &ReadOptions::default())
+}
+
+/// Reads every puzzle in the file.
+pub fn read_puzzles(filename: &str, options: &ReadOptions) -> Result<Vec<Puzzle>> {
+    let puzzles = open_puzzles(filename, options)?.collect::<Result<Vec<Puzzle>>>()?;
 
     if puzzles.is_empty() {
         return Err(Error::Generic("No puzzles found in the file".to_string()));
@@ -60,3 +162,567 @@ pub fn read_sudoku_puzzles(filename: &str) -> Result<Vec<Puzzle>> {
 
     Ok(puzzles)
 }
+
+/// Opens a puzzle file for streaming with the built-in formats, with "-" reading from stdin.
+pub fn open_puzzles(filename: &str, options: &ReadOptions) -> Result<PuzzleStream> {
+    let context = ParseContext {
+        path: match filename {
+            "-" => "<stdin>".to_string(),
+            path => path.to_string(),
+        },
+        strict: options.strict,
+    };
+    FormatRegistry::new().read(open_source(filename)?, &options.format, context)
+}
+
+/// A buffered reader over the file, or stdin for "-".
+pub fn open_source(filename: &str) -> Result<Box<dyn BufRead>> {
+    Ok(if filename == "-" {
+        Box::new(io::stdin().lock())
+    } else {
+        Box::new(BufReader::new(File::open(filename).map_err(Error::IO)?))
+    })
+}
+
+/// The column, counting characters from 1, of the byte at `index` in `text`, so errors after
+/// non-ASCII text point at the right character.
+pub fn column_at(text: &str, index: usize) -> usize {
+    text.char_indices().take_while(|&(i, _)| i < index).count() + 1
+}
+
+/// A digit or `.`, the characters of a cell in the compact formats.
+pub fn is_cell_char(c: char) -> bool {
+    c.is_ascii_digit() || c == '.'
+}
+
+/// Whether `c` can be a cell of a grid `size` cells across: `0`, `.` or one of its digits.
+fn is_cell_char_of(c: char, size: usize) -> bool {
+    c == '0' || c == '.' || digit_value(c).is_some_and(|digit| digit as usize <= size)
+}
+
+/// Reads puzzles one at a time from any buffered source, so only the puzzle being
+/// parsed is held in memory.
+///
+/// Two layouts are recognised and can be mixed in one source:
+/// - a header line containing a letter followed by the rows of the grid, as many as the first
+///   row has cells (4, 6, 9, 12, 16 or 25) unless the header gives the boxes, and for puzzles
+///   tagged `[killer]` a cage map as read by `Cage::parse_map`, or for puzzles tagged
+///   `[sandwich]` clue lines as read by `Sandwiches::parse`, or for puzzles tagged `[jigsaw]`
+///   a region map as read by `Regions::parse`. Puzzles tagged `[x-sudoku]` need nothing
+///   more, their diagonals being fixed;
+/// - one puzzle per line as all its cells, e.g. 81 characters, with `0` or `.` for blanks,
+///   optionally followed by whitespace and a comment holding its header.
+///
+/// Digits past 9 are the letters from `A`, as written by `digit_char`.
+///
+/// Headers are a name and optional tags, as read by `Metadata::parse`.
+///
+/// Lines starting with `#` are comments. Any other lines are skipped, or rejected in strict
+/// mode unless blank.
+pub struct PuzzleReader<R: BufRead> {
+    lines: Lines<R>,
+    line_number: usize,
+    context: ParseContext,
+}
+
+impl<R: BufRead> PuzzleReader<R> {
+    pub fn new(reader: R, context: ParseContext) -> PuzzleReader<R> {
+        PuzzleReader {
+            lines: reader.lines(),
+            line_number: 0,
+            context,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<Result<String>> {
+        let line = self.lines.next()?;
+        self.line_number += 1;
+        Some(line.map_err(Error::IO))
+    }
+
+    fn read_puzzle(&mut self, header: &str) -> Result<Puzzle> {
+        let header_line = self.line_number;
+        let metadata = Metadata::parse(header, &self.context, header_line, 1)?;
+        let mut grid: Grid = Vec::new();
+
+        // Without boxes in the header, the first row's length gives the size
+        let mut size = metadata.boxes.map_or(9, |shape| shape.size());
+        while grid.len() < size {
+            let Some(row) = self.next_line() else {
+                return Err(ParseError::MissingRows {
+                    at: self.context.at(self.line_number + 1, 1),
+                    puzzle: metadata.name,
+                    found: grid.len(),
+                    expected: size,
+                }
+                .into());
+            };
+            let row = row?;
+            let length = row.chars().count();
+            if grid.is_empty() && metadata.boxes.is_none() && Shape::SIZES.contains(&length) {
+                size = length;
+            }
+            if length != size {
+                return Err(ParseError::RowLength {
+                    at: self.context.at(self.line_number, 1),
+                    puzzle: metadata.name,
+                    row: grid.len() + 1,
+                    found: length,
+                    expected: size,
+                    text: row,
+                }
+                .into());
+            }
+
+            grid.push(self.context.cells(&row, size, self.line_number, 1)?);
+        }
+
+        let shape = self.context.shape(&metadata, size, header_line, 1)?;
+        let constraints = match metadata.variation {
+            Some(Variation::Killer) => Constraints {
+                cages: self.read_cages(&metadata.name)?,
+                ..Constraints::default()
+            },
+            Some(Variation::Sandwhich) => Constraints {
+                sandwiches: self.read_sandwiches(&metadata.name)?,
+                ..Constraints::default()
+            },
+            Some(Variation::Jigsaw) => Constraints {
+                regions: Some(self.read_regions(&metadata.name, size)?),
+                ..Constraints::default()
+            },
+            _ => Constraints::default(),
+        };
+        let constraints = Constraints {
+            shape,
+            ..constraints
+        };
+        self.context
+            .check_variation(&metadata, &constraints, header_line, 1)?;
+        Ok(Puzzle::from_metadata(metadata, grid, constraints))
+    }
+
+    /// Reads the cage map that follows the rows of a killer puzzle.
+    fn read_cages(&mut self, puzzle: &str) -> Result<Vec<Cage>> {
+        let mut lines = Vec::with_capacity(10);
+        while lines.len() < 10 {
+            let Some(line) = self.next_line() else {
+                return Err(ParseError::MissingCages {
+                    at: self.context.at(self.line_number + 1, 1),
+                    puzzle: puzzle.to_string(),
+                    found: lines.len(),
+                }
+                .into());
+            };
+            lines.push(line?);
+        }
+
+        let sums = lines.pop().unwrap_or_default();
+        Cage::parse_map(&lines, &sums, &self.context, puzzle, self.line_number - 9)
+    }
+
+    /// Reads the two clue lines that follow the rows of a sandwich puzzle.
+    fn read_sandwiches(&mut self, puzzle: &str) -> Result<Sandwiches> {
+        let mut lines = Vec::with_capacity(2);
+        while lines.len() < 2 {
+            let Some(line) = self.next_line() else {
+                return Err(ParseError::MissingClues {
+                    at: self.context.at(self.line_number + 1, 1),
+                    puzzle: puzzle.to_string(),
+                    found: lines.len(),
+                }
+                .into());
+            };
+            lines.push(line?);
+        }
+        Sandwiches::parse(&lines, &self.context, self.line_number - 1)
+    }
+
+    /// Reads the region map that follows the rows of a jigsaw puzzle, one row for each of
+    /// the grid's `size` rows.
+    fn read_regions(&mut self, puzzle: &str, size: usize) -> Result<Regions> {
+        let mut rows = Vec::with_capacity(size);
+        while rows.len() < size {
+            let Some(row) = self.next_line() else {
+                return Err(ParseError::MissingRegions {
+                    at: self.context.at(self.line_number + 1, 1),
+                    puzzle: puzzle.to_string(),
+                    found: rows.len(),
+                    expected: size,
+                }
+                .into());
+            };
+            rows.push(row?);
+        }
+        Regions::parse(&rows, &self.context, puzzle, self.line_number + 1 - size)
+    }
+
+    /// Parses a one-line puzzle. Its comment is read as a header, and without one the
+    /// puzzle is named after its line number.
+    fn read_line_puzzle(&self, line: &str) -> Result<Puzzle> {
+        let (cells, comment) = line.split_at(line.find(char::is_whitespace).unwrap_or(line.len()));
+        let grid = self.context.grid(cells, self.line_number, 1)?;
+
+        let header = comment.trim_start().trim_start_matches('#').trim_start();
+        let column = column_at(line, line.len() - header.len());
+        let metadata = match header.trim_end() {
+            "" => Metadata::named(format!("Line {}", self.line_number)),
+            header => Metadata::parse(header, &self.context, self.line_number, column)?,
+        };
+        let constraints = Constraints {
+            shape: self
+                .context
+                .shape(&metadata, grid.len(), self.line_number, column)?,
+            ..Constraints::default()
+        };
+        self.context
+            .check_variation(&metadata, &constraints, self.line_number, column)?;
+        Ok(Puzzle::from_metadata(metadata, grid, constraints))
+    }
+}
+
+/// Whether a line holds a whole puzzle: the cells of a grid of one of the supported sizes,
+/// e.g. 81 digits or `.`, then nothing or whitespace.
+pub fn is_line_puzzle(line: &str) -> bool {
+    let cells = &line[..line.find(char::is_whitespace).unwrap_or(line.len())];
+    Shape::SIZES
+        .into_iter()
+        .any(|size| cells.len() == size * size && cells.chars().all(|c| is_cell_char_of(c, size)))
+}
+
+impl<R: BufRead> Iterator for PuzzleReader<R> {
+    type Item = Result<Puzzle>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(line) = self.next_line() {
+            match line {
+                Ok(line) if line.starts_with('#') => continue,
+                Ok(line) if is_line_puzzle(&line) => return Some(self.read_line_puzzle(&line)),
+                Ok(line) if line.chars().any(|c| c.is_alphabetic()) => {
+                    return Some(self.read_puzzle(&line));
+                }
+                Ok(line) if self.context.strict && !line.trim().is_empty() => {
+                    return Some(Err(ParseError::UnexpectedLine {
+                        at: self.context.at(self.line_number, 1),
+                        text: line,
+                    }
+                    .into()));
+                }
+                Ok(_) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const EMPTY_ROWS: &str = "000000000\n000000000\n000000000\n000000000\n000000000\n000000000\n000000000\n000000000\n000000000\n";
+
+    fn read(text: &str, format: &str, strict: bool) -> Result<Vec<Puzzle>> {
+        let context = ParseContext {
+            path: "test.txt".to_string(),
+            strict,
+        };
+        let reader = Box::new(Cursor::new(text.to_string()));
+        FormatRegistry::new()
+            .read(reader, format, context)?
+            .collect()
+    }
+
+    /// The error reading `text` fails with, and the line and column its message starts with.
+    fn parse_error(text: &str, format: &str, strict: bool) -> (ParseError, usize, usize) {
+        let error = match read(text, format, strict) {
+            Err(Error::Parse(error)) => error,
+            Err(other) => panic!("expected a parse error, got {}", other),
+            Ok(_) => panic!("expected a parse error reading {:?}", text),
+        };
+        let message = error.to_string();
+        let mut parts = message.strip_prefix("test.txt:").unwrap().splitn(3, ':');
+        let line = parts.next().unwrap().parse().unwrap();
+        let column = parts.next().unwrap().parse().unwrap();
+        (error, line, column)
+    }
+
+    #[test]
+    fn short_row() {
+        let (error, line, column) = parse_error("Mini\n1234\n341\n", "auto", false);
+        assert!(matches!(
+            error,
+            ParseError::RowLength {
+                row: 2,
+                found: 3,
+                expected: 4,
+                ..
+            }
+        ));
+        assert_eq!((line, column), (3, 1));
+    }
+
+    #[test]
+    fn missing_rows() {
+        let (error, line, column) = parse_error("Mini\n1234\n3412\n", "auto", false);
+        assert!(matches!(
+            error,
+            ParseError::MissingRows {
+                found: 2,
+                expected: 4,
+                ..
+            }
+        ));
+        assert_eq!((line, column), (4, 1));
+    }
+
+    #[test]
+    fn cell_count() {
+        let text = "[\n  {\"name\": \"J\", \"puzzle\": \"12345\"}\n]";
+        let (error, line, column) = parse_error(text, "auto", false);
+        assert!(matches!(error, ParseError::CellCount { found: 5, .. }));
+        assert_eq!((line, column), (2, 28));
+    }
+
+    #[test]
+    fn unknown_character_is_a_blank_unless_strict() {
+        let text = "Mini\n12x4\n3412\n2143\n4321\n";
+        let puzzles = read(text, "auto", false).unwrap();
+        assert_eq!(puzzles[0].board().grid[0], vec![1, 2, 0, 4]);
+
+        let (error, line, column) = parse_error(text, "auto", true);
+        assert!(matches!(
+            error,
+            ParseError::UnknownCharacter { found: 'x', .. }
+        ));
+        assert_eq!((line, column), (2, 3));
+    }
+
+    #[test]
+    fn digit_out_of_range_in_both_modes() {
+        for strict in [false, true] {
+            let (error, line, column) = parse_error("Mini\n1234\n3472\n", "auto", strict);
+            assert!(matches!(
+                error,
+                ParseError::DigitOutOfRange {
+                    found: '7',
+                    size: 4,
+                    ..
+                }
+            ));
+            assert_eq!((line, column), (3, 3));
+        }
+    }
+
+    #[test]
+    fn unexpected_line_is_skipped_unless_strict() {
+        let text = "12 34\nMini\n1234\n3412\n2143\n4321\n";
+        assert_eq!(read(text, "auto", false).unwrap().len(), 1);
+
+        let (error, line, column) = parse_error(text, "grid", true);
+        assert!(matches!(error, ParseError::UnexpectedLine { .. }));
+        assert_eq!((line, column), (1, 1));
+    }
+
+    #[test]
+    fn unknown_tag_stays_in_the_name_unless_strict() {
+        let text = "Mini (from book) [page 3] [easy]\n1234\n3412\n2143\n4321\n";
+        let puzzles = read(text, "auto", false).unwrap();
+        assert_eq!(puzzles[0].name(), "Mini (from book) [page 3]");
+        assert_eq!(puzzles[0].difficulty(), Some(Difficulty::Easy));
+
+        let (error, line, column) = parse_error(text, "auto", true);
+        assert!(matches!(error, ParseError::UnknownTag { .. }));
+        assert_eq!((line, column), (1, 18));
+    }
+
+    /// Columns count characters, not bytes, wherever the error is found.
+    #[test]
+    fn columns_count_characters() {
+        let text = "Grillé été [foo]\n1234\n3412\n2143\n4321\n";
+        let (error, line, column) = parse_error(text, "auto", true);
+        assert!(matches!(error, ParseError::UnknownTag { .. }));
+        assert_eq!((line, column), (1, 12));
+
+        let text = "1234341221434321 # Grillé [foo]\n";
+        let (error, line, column) = parse_error(text, "auto", true);
+        assert!(matches!(error, ParseError::UnknownTag { .. }));
+        assert_eq!((line, column), (1, 27));
+
+        let map = "ééééééééé\n".repeat(9);
+        let text = format!("Killer [killer]\n{}{}é=45 b\n", EMPTY_ROWS, map);
+        let (error, line, column) = parse_error(&text, "auto", false);
+        assert!(matches!(error, ParseError::InvalidCage { .. }));
+        assert_eq!((line, column), (20, 6));
+
+        let text = "[\n  {\"name\": \"é\", \"puzzle\": \"12345\"}\n]";
+        let (error, line, column) = parse_error(text, "json", false);
+        assert!(matches!(error, ParseError::CellCount { .. }));
+        assert_eq!((line, column), (2, 28));
+        let (error, line, column) = parse_error("[\n  {\"é\": }\n]", "json", false);
+        assert!(matches!(error, ParseError::Json { .. }));
+        assert_eq!((line, column), (2, 9));
+    }
+
+    #[test]
+    fn box_shape() {
+        let (error, line, column) =
+            parse_error("1234341221434321 # Mini [boxes=3x3]\n", "auto", false);
+        assert!(matches!(error, ParseError::BoxShape { size: 4, .. }));
+        assert_eq!((line, column), (1, 20));
+    }
+
+    #[test]
+    fn unsupported_size() {
+        let (error, line, column) =
+            parse_error("Mini [killer]\n1234\n3412\n2143\n4321\n", "auto", false);
+        assert!(matches!(error, ParseError::UnsupportedSize { size: 4, .. }));
+        assert_eq!((line, column), (1, 1));
+    }
+
+    #[test]
+    fn missing_cages() {
+        let text = format!("Killer [killer]\n{}aabbccdde\n", EMPTY_ROWS);
+        let (error, line, column) = parse_error(&text, "auto", false);
+        assert!(matches!(error, ParseError::MissingCages { found: 1, .. }));
+        assert_eq!((line, column), (12, 1));
+    }
+
+    #[test]
+    fn killer_tag_without_cages() {
+        let line = format!("{} # Killer [killer]\n", ".".repeat(81));
+        let (error, at_line, column) = parse_error(&line, "auto", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+        assert_eq!((at_line, column), (1, 85));
+
+        let sdk = format!("#DKiller [killer]\n{}", EMPTY_ROWS.replace('0', "."));
+        let (error, ..) = parse_error(&sdk, "sdk", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+
+        let json = format!(
+            "[{{\"name\": \"Killer\", \"puzzle\": \"{}\", \"variation\": \"killer\"}}]",
+            "0".repeat(81)
+        );
+        let (error, ..) = parse_error(&json, "json", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+    }
+
+    #[test]
+    fn invalid_cage() {
+        let map = "aaaaaaaaa\n".repeat(9);
+        let text = format!("Killer [killer]\n{}{}a=45 b\n", EMPTY_ROWS, map);
+        let (error, line, column) = parse_error(&text, "auto", false);
+        assert!(matches!(error, ParseError::InvalidCage { .. }));
+        assert_eq!((line, column), (20, 6));
+    }
+
+    #[test]
+    fn missing_clues() {
+        let text = format!(
+            "Sandwich [sandwich]\n{}rows . . . . . . . . .\n",
+            EMPTY_ROWS
+        );
+        let (error, line, column) = parse_error(&text, "auto", false);
+        assert!(matches!(error, ParseError::MissingClues { found: 1, .. }));
+        assert_eq!((line, column), (12, 1));
+    }
+
+    #[test]
+    fn sandwich_tag_without_clues() {
+        let line = format!("{} # S [sandwich]\n", ".".repeat(81));
+        let (error, line, column) = parse_error(&line, "auto", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+        assert_eq!((line, column), (1, 85));
+
+        let clues = "rows . . . . . . . . .\ncolumns . . . . . . . . .\n";
+        let grid = format!("S [sandwich]\n{}{}", EMPTY_ROWS, clues);
+        let (error, line, column) = parse_error(&grid, "auto", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+        assert_eq!((line, column), (1, 1));
+    }
+
+    #[test]
+    fn invalid_clue() {
+        let text = format!(
+            "Sandwich [sandwich]\n{}rows . . . . . . . . .\ncolumns . 1 . . . . . . .\n",
+            EMPTY_ROWS
+        );
+        let (error, line, column) = parse_error(&text, "auto", false);
+        assert!(matches!(error, ParseError::InvalidClue { .. }));
+        assert_eq!((line, column), (12, 11));
+    }
+
+    #[test]
+    fn missing_regions() {
+        let text = "Mini [jigsaw]\n1234\n3412\n2143\n4321\n1122\n";
+        let (error, line, column) = parse_error(text, "auto", false);
+        assert!(matches!(
+            error,
+            ParseError::MissingRegions {
+                found: 1,
+                expected: 4,
+                ..
+            }
+        ));
+        assert_eq!((line, column), (7, 1));
+    }
+
+    #[test]
+    fn jigsaw_tag_without_regions() {
+        let line = format!("{} # L [jigsaw]\n", ".".repeat(81));
+        let (error, line, column) = parse_error(&line, "auto", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+        assert_eq!((line, column), (1, 85));
+
+        let json = format!(
+            "[{{\"name\": \"L\", \"puzzle\": \"{}\", \"variation\": \"jigsaw\"}}]",
+            "0".repeat(81)
+        );
+        let (error, ..) = parse_error(&json, "json", false);
+        assert!(matches!(error, ParseError::MissingConstraints { .. }));
+    }
+
+    #[test]
+    fn invalid_region() {
+        let text = "Mini [jigsaw]\n0000\n0000\n0000\n0000\n1122\n2211\n3344\n3344\n";
+        let (error, line, column) = parse_error(text, "auto", false);
+        assert!(matches!(error, ParseError::InvalidRegion { .. }));
+        assert_eq!((line, column), (6, 1));
+    }
+
+    #[test]
+    fn exported_puzzles_read_back() {
+        let text = format!("First\n{}Second [hard]\n1{}", EMPTY_ROWS, &EMPTY_ROWS[1..]);
+        let registry = FormatRegistry::new();
+        for format in ["grid", "line", "sdk", "json"] {
+            let puzzles = Box::new(read(&text, "auto", true).unwrap().into_iter().map(Ok));
+            let mut output = Vec::new();
+            registry
+                .get(format)
+                .unwrap()
+                .write(&mut output, puzzles)
+                .unwrap();
+
+            let written = read(&String::from_utf8(output).unwrap(), "auto", true).unwrap();
+            let headers: Vec<String> = written.iter().map(Puzzle::header).collect();
+            assert_eq!(headers, ["First", "Second [hard]"], "{}", format);
+            assert_eq!(written[1].board().grid[0][0], 1, "{}", format);
+        }
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(matches!(
+            read("", "foo", false),
+            Err(Error::UnknownFormat { name, .. }) if name == "foo"
+        ));
+    }
+
+    #[test]
+    fn invalid_json() {
+        let (error, line, column) = parse_error("[\n  {\"name\": }\n]", "auto", false);
+        assert!(matches!(error, ParseError::Json { .. }));
+        assert_eq!((line, column), (2, 12));
+    }
+}
@@ -0,0 +1,154 @@
+//! Jigsaw Sudoku regions: irregular shapes of connected cells that take the place of the boxes
+
+use crate::prelude::*;
+
+/// Which region each cell belongs to, numbered from 0 in the order the regions first appear
+/// reading row by row.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Regions(Vec<Vec<u8>>);
+
+impl Regions {
+    /// The ordinary boxes of a board, as regions.
+    pub fn boxes(shape: Shape) -> Regions {
+        let size = shape.size();
+        let per_band = size / shape.box_width;
+        let map = (0..size)
+            .map(|row| {
+                (0..size)
+                    .map(|col| (row / shape.box_height * per_band + col / shape.box_width) as u8)
+                    .collect()
+            })
+            .collect();
+        Regions(map)
+    }
+
+    /// Regions from a square map naming each cell's region with any character. Fails with the
+    /// offending name unless there are as many regions as rows, each of as many orthogonally
+    /// connected cells.
+    pub fn from_ids(ids: &[Vec<char>]) -> core::result::Result<Regions, (char, String)> {
+        let size = ids.len();
+        let mut names: Vec<char> = Vec::new();
+        let mut map = vec![vec![0; size]; size];
+        for (row, ids_in_row) in ids.iter().enumerate() {
+            for (col, &id) in ids_in_row.iter().enumerate() {
+                let index = match names.iter().position(|&known| known == id) {
+                    Some(index) => index,
+                    None if names.len() == size => {
+                        return Err((id, format!("is one too many, there must be {}", size)));
+                    }
+                    None => {
+                        names.push(id);
+                        names.len() - 1
+                    }
+                };
+                map[row][col] = index as u8;
+            }
+        }
+
+        let regions = Regions(map);
+        for (index, &name) in names.iter().enumerate() {
+            let cells = regions.cells(index);
+            if cells.len() != size {
+                return Err((
+                    name,
+                    format!("has {} cells instead of {}", cells.len(), size),
+                ));
+            }
+            if !is_connected(&cells) {
+                return Err((name, "isn't connected".to_string()));
+            }
+        }
+        Ok(regions)
+    }
+
+    /// Reads a region map: for each row of the board, a row of characters naming each cell's
+    /// region, e.g. `111222333`. `line` is the line number of the first row.
+    pub fn parse(
+        rows: &[String],
+        context: &ParseContext,
+        puzzle: &str,
+        line: usize,
+    ) -> Result<Regions> {
+        let size = rows.len();
+        let mut ids = Vec::with_capacity(size);
+        for (row, text) in rows.iter().enumerate() {
+            let ids_in_row: Vec<char> = text.trim_end().chars().collect();
+            if ids_in_row.len() != size {
+                return Err(ParseError::RowLength {
+                    at: context.at(line + row, 1),
+                    puzzle: puzzle.to_string(),
+                    row: row + 1,
+                    found: ids_in_row.len(),
+                    expected: size,
+                    text: text.clone(),
+                }
+                .into());
+            }
+            ids.push(ids_in_row);
+        }
+
+        Self::from_ids(&ids).map_err(|(id, reason)| {
+            // Point at the region's first cell
+            let (row, col) = (0..size * size)
+                .map(|i| (i / size, i % size))
+                .find(|&(r, c)| ids[r][c] == id)
+                .unwrap_or_default();
+            ParseError::InvalidRegion {
+                at: context.at(line + row, col + 1),
+                region: id.to_string(),
+                reason,
+            }
+            .into()
+        })
+    }
+
+    /// Rows and columns of the board the regions cover.
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The region containing (row, col).
+    pub fn region(&self, row: usize, col: usize) -> usize {
+        self.0[row][col] as usize
+    }
+
+    /// The cells of a region in reading order.
+    pub fn cells(&self, region: usize) -> Vec<(usize, usize)> {
+        let size = self.size();
+        (0..size * size)
+            .map(|i| (i / size, i % size))
+            .filter(|&(row, col)| self.region(row, col) == region)
+            .collect()
+    }
+
+    /// The rows `parse` reads, regions named like the digits from 1 up.
+    pub fn to_lines(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|row| row.iter().map(|&region| digit_char(region + 1)).collect())
+            .collect()
+    }
+}
+
+/// Whether every cell can be reached from the first through orthogonal steps within `cells`.
+fn is_connected(cells: &[(usize, usize)]) -> bool {
+    let mut reached = vec![cells[0]];
+    let mut next = 0;
+    while let Some(&(row, col)) = reached.get(next) {
+        next += 1;
+        for &cell in cells {
+            let adjacent = row.abs_diff(cell.0) + col.abs_diff(cell.1) == 1;
+            if adjacent && !reached.contains(&cell) {
+                reached.push(cell);
+            }
+        }
+    }
+    reached.len() == cells.len()
+}
+
+impl SudokuBoard {
+    /// The jigsaw regions, `None` for puzzles with the standard boxes.
+    pub fn regions(&self) -> Option<&Regions> {
+        self.units.regions()
+    }
+}
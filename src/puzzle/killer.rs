@@ -0,0 +1,459 @@
+//! Killer Sudoku cages: groups of cells whose digits don't repeat and add up to a given sum
+
+use crate::prelude::*;
+
+/// Cells whose digits are all different and add up to `sum`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cage {
+    pub sum: u8,
+    pub cells: Vec<(usize, usize)>,
+}
+
+/// Sum of the digits 1-9, the total of every row, column and box.
+const HOUSE_SUM: u8 = 45;
+
+/// Characters naming cages in a cage map, assigned in this order on export. There are enough
+/// for a cage in every cell.
+const CAGE_IDS: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789\
+                        !\"$%&'()*+,-/:;<>?@[\\]^_`{|}~";
+
+impl Cage {
+    pub fn new(sum: u8, cells: Vec<(usize, usize)>) -> Cage {
+        Cage { sum, cells }
+    }
+
+    /// Why the cage can't be filled with different digits, if it can't.
+    fn check(&self) -> core::result::Result<(), String> {
+        if self.cells.len() > 9 {
+            return Err(format!("has {} cells, more than 9", self.cells.len()));
+        }
+        let masks = vec![ALL_DIGITS; self.cells.len()];
+        if sum_candidates(&masks, self.sum).contains(&0) {
+            return Err(format!(
+                "can't total {} with {} different digits",
+                self.sum,
+                self.cells.len()
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reads a cage map: 9 rows of 9 characters naming each cell's cage, `.` for no cage,
+    /// then a line of `id=sum` pairs separated by spaces, e.g. `a=3 b=15`. Cages are numbered
+    /// in the order they first appear. `line` is the line number of the first row.
+    pub fn parse_map(
+        rows: &[String],
+        sums: &str,
+        context: &ParseContext,
+        puzzle: &str,
+        line: usize,
+    ) -> Result<Vec<Cage>> {
+        let mut ids: Vec<char> = Vec::new();
+        let mut cells: Vec<Vec<(usize, usize)>> = Vec::new();
+        for (row, text) in rows.iter().enumerate() {
+            let ids_in_row: Vec<char> = text.trim_end().chars().collect();
+            if ids_in_row.len() != 9 {
+                return Err(ParseError::RowLength {
+                    at: context.at(line + row, 1),
+                    puzzle: puzzle.to_string(),
+                    row: row + 1,
+                    found: ids_in_row.len(),
+                    expected: 9,
+                    text: text.clone(),
+                }
+                .into());
+            }
+            for (col, id) in ids_in_row.into_iter().enumerate() {
+                if id == '.' {
+                    continue;
+                }
+                match ids.iter().position(|&known| known == id) {
+                    Some(index) => cells[index].push((row, col)),
+                    None => {
+                        ids.push(id);
+                        cells.push(vec![(row, col)]);
+                    }
+                }
+            }
+        }
+
+        let sums_line = line + rows.len();
+        let mut cage_sums: Vec<Option<u8>> = vec![None; ids.len()];
+        let mut rest = sums;
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            let token_start = sums.len() - rest.len() + start;
+            let token = rest[start..].split_whitespace().next().unwrap_or_default();
+            rest = &rest[start + token.len()..];
+
+            let invalid = |cage: &str, reason: &str| -> Error {
+                ParseError::InvalidCage {
+                    at: context.at(sums_line, column_at(sums, token_start)),
+                    cage: cage.to_string(),
+                    reason: reason.to_string(),
+                }
+                .into()
+            };
+            let Some((id, sum)) = token.split_once('=') else {
+                return Err(invalid(token, "isn't written as id=sum"));
+            };
+            let index = id
+                .chars()
+                .next()
+                .filter(|_| id.chars().count() == 1)
+                .and_then(|id| ids.iter().position(|&known| known == id))
+                .ok_or_else(|| invalid(id, "has a sum but no cells"))?;
+            let sum = sum
+                .parse()
+                .map_err(|_| invalid(id, &format!("has an invalid sum '{}'", sum)))?;
+            if cage_sums[index].replace(sum).is_some() {
+                return Err(invalid(id, "has more than one sum"));
+            }
+        }
+
+        ids.into_iter()
+            .zip(cells)
+            .zip(cage_sums)
+            .map(|((id, cells), sum)| {
+                let invalid = |reason: String| -> Error {
+                    ParseError::InvalidCage {
+                        at: context.at(sums_line, 1),
+                        cage: id.to_string(),
+                        reason,
+                    }
+                    .into()
+                };
+                let cage = Cage::new(sum.ok_or_else(|| invalid("has no sum".to_string()))?, cells);
+                cage.check().map_err(invalid)?;
+                Ok(cage)
+            })
+            .collect()
+    }
+
+    /// Checks cages read from elsewhere than a cage map, which can overlap.
+    pub fn check_all(
+        cages: &[Cage],
+        context: &ParseContext,
+        line: usize,
+        column: usize,
+    ) -> Result<()> {
+        let mut seen = [[false; 9]; 9];
+        for (index, cage) in cages.iter().enumerate() {
+            let invalid = |reason: String| -> Error {
+                ParseError::InvalidCage {
+                    at: context.at(line, column),
+                    cage: (index + 1).to_string(),
+                    reason,
+                }
+                .into()
+            };
+            for &(row, col) in &cage.cells {
+                if std::mem::replace(&mut seen[row][col], true) {
+                    return Err(invalid(format!(
+                        "overlaps another cage at r{}c{}",
+                        row + 1,
+                        col + 1
+                    )));
+                }
+            }
+            cage.check().map_err(invalid)?;
+        }
+        Ok(())
+    }
+}
+
+/// The cage map lines `Cage::parse_map` reads: 9 rows of cage ids, then the sums.
+pub fn cage_map(cages: &[Cage]) -> Vec<String> {
+    let ids: Vec<char> = CAGE_IDS.chars().collect();
+    let mut rows = [['.'; 9]; 9];
+    for (index, cage) in cages.iter().enumerate() {
+        for &(row, col) in &cage.cells {
+            rows[row][col] = ids[index];
+        }
+    }
+
+    let sums: Vec<String> = cages
+        .iter()
+        .enumerate()
+        .map(|(index, cage)| format!("{}={}", ids[index], cage.sum))
+        .collect();
+    rows.iter()
+        .map(|row| row.iter().collect())
+        .chain(std::iter::once(sums.join(" ")))
+        .collect()
+}
+
+/// Reads a cell written as `r1c1`.
+pub fn parse_cell(text: &str) -> Option<(usize, usize)> {
+    let (row, col) = text.strip_prefix('r')?.split_once('c')?;
+    let (row, col): (usize, usize) = (row.parse().ok()?, col.parse().ok()?);
+    ((1..=9).contains(&row) && (1..=9).contains(&col)).then(|| (row - 1, col - 1))
+}
+
+/// Given the digits each cell may hold, the digits each cell can take in some assignment of
+/// different digits adding up to `sum`. Every mask is 0 when there is no such assignment.
+pub fn sum_candidates(masks: &[Mask], sum: u8) -> Vec<Mask> {
+    let mut allowed = vec![0; masks.len()];
+    let mut memo = vec![None; (masks.len() + 1) << 9];
+    sum_search(masks, sum, 0, 0, 0, &mut memo, &mut allowed);
+    allowed
+}
+
+/// Whether cells from `cell` on can complete the sum, given the digits `used` so far (which
+/// add up to `total`). Every digit that can is recorded in `allowed`. Results are memoised by
+/// cell and digits used, since the total follows from the digits.
+fn sum_search(
+    masks: &[Mask],
+    sum: u8,
+    cell: usize,
+    used: Mask,
+    total: u8,
+    memo: &mut [Option<bool>],
+    allowed: &mut [Mask],
+) -> bool {
+    if cell == masks.len() {
+        return total == sum;
+    }
+    let key = (cell << 9) | used as usize;
+    if let Some(feasible) = memo[key] {
+        return feasible;
+    }
+
+    let mut feasible = false;
+    for num in digits(masks[cell] & !used) {
+        if total + num > sum {
+            break;
+        }
+        if sum_search(
+            masks,
+            sum,
+            cell + 1,
+            used | bit(num),
+            total + num,
+            memo,
+            allowed,
+        ) {
+            allowed[cell] |= bit(num);
+            feasible = true;
+        }
+    }
+    memo[key] = Some(feasible);
+    feasible
+}
+
+impl SudokuBoard {
+    /// The killer cages, empty for other variations.
+    pub fn cages(&self) -> &[Cage] {
+        self.units.cages()
+    }
+
+    /// Candidates of `cells`, which must all see each other, that no combination of different
+    /// digits adding up to `sum` can use. `None` if there are none or no combination fits.
+    fn sum_eliminations(
+        &self,
+        cells: &[(usize, usize)],
+        sum: u8,
+    ) -> Option<Vec<(usize, usize, u8)>> {
+        let masks = self.cell_masks(cells);
+        let allowed = sum_candidates(&masks, sum);
+        if allowed.contains(&0) {
+            return None;
+        }
+
+        let eliminations: Vec<(usize, usize, u8)> = cells
+            .iter()
+            .zip(allowed)
+            .filter(|&(&(r, c), _)| self.grid[r][c] == 0)
+            .flat_map(|(&(r, c), allowed)| {
+                digits(self.candidates[r][c] & !allowed).map(move |num| (r, c, num))
+            })
+            .collect();
+        (!eliminations.is_empty()).then_some(eliminations)
+    }
+
+    /// Cage Combinations: A candidate that appears in no combination of different digits
+    /// adding up to its cage's sum, given the candidates of the other cells in the cage.
+    pub fn find_cage_combination(&self) -> Option<SolveStep> {
+        for (index, cage) in self.cages().iter().enumerate() {
+            if let Some(eliminations) = self.sum_eliminations(&cage.cells, cage.sum) {
+                return Some(SolveStep {
+                    eliminations,
+                    pattern: cage.cells.clone(),
+                    houses: vec![House::Cage(index)],
+                    ..SolveStep::new("cage_combinations")
+                });
+            }
+        }
+        None
+    }
+
+    /// Innies and Outies, the rule of 45: Every house adds up to 45. Taking away the cages
+    /// lying wholly inside a house leaves its innies, whose sum is then known. Adding the
+    /// cages that stick out of the house instead gives the sum of the outies, the cells
+    /// sticking out. Either group that can't repeat a digit is treated like a cage.
+    pub fn find_innie_outie(&self) -> Option<SolveStep> {
+        if self.cages().is_empty() {
+            return None;
+        }
+
+        for (index, house) in self.units.houses().enumerate() {
+            let mut touching: Vec<usize> = house
+                .iter()
+                .filter_map(|&(r, c)| self.units.cage_of(r, c))
+                .collect();
+            touching.sort_unstable();
+            touching.dedup();
+
+            let (inside, crossing): (Vec<usize>, Vec<usize>) =
+                touching.into_iter().partition(|&cage| {
+                    self.cages()[cage]
+                        .cells
+                        .iter()
+                        .all(|cell| house.contains(cell))
+                });
+            let inside_sum: u32 = inside
+                .iter()
+                .map(|&cage| self.cages()[cage].sum as u32)
+                .sum();
+            let innies: Vec<(usize, usize)> = house
+                .iter()
+                .copied()
+                .filter(|&(r, c)| {
+                    self.units
+                        .cage_of(r, c)
+                        .is_none_or(|cage| !inside.contains(&cage))
+                })
+                .collect();
+            let label = self.units.label(index);
+
+            // A whole house of innies is just the house itself
+            if !inside.is_empty() && !innies.is_empty() {
+                if let Some(step) = (HOUSE_SUM as u32)
+                    .checked_sub(inside_sum)
+                    .and_then(|sum| self.sum_step("innies", &innies, sum as u8, label))
+                {
+                    return Some(step);
+                }
+            }
+
+            let all_caged = innies
+                .iter()
+                .all(|&(r, c)| self.units.cage_of(r, c).is_some());
+            if crossing.is_empty() || !all_caged {
+                continue;
+            }
+            let outies: Vec<(usize, usize)> = crossing
+                .iter()
+                .flat_map(|&cage| self.cages()[cage].cells.iter().copied())
+                .filter(|cell| !house.contains(cell))
+                .collect();
+            let crossing_sum: u32 = crossing
+                .iter()
+                .map(|&cage| self.cages()[cage].sum as u32)
+                .sum();
+            let Some(sum) = (crossing_sum + inside_sum).checked_sub(HOUSE_SUM as u32) else {
+                continue;
+            };
+            let distinct = outies
+                .iter()
+                .enumerate()
+                .all(|(i, &a)| outies[i + 1..].iter().all(|&b| self.units.sees(a, b)));
+            if distinct {
+                if let Some(step) = u8::try_from(sum)
+                    .ok()
+                    .and_then(|sum| self.sum_step("outies", &outies, sum, label))
+                {
+                    return Some(step);
+                }
+            }
+        }
+        None
+    }
+
+    /// A step removing the candidates of `cells` that can't be part of `sum`.
+    fn sum_step(
+        &self,
+        technique: &'static str,
+        cells: &[(usize, usize)],
+        sum: u8,
+        house: House,
+    ) -> Option<SolveStep> {
+        let eliminations = self.sum_eliminations(cells, sum)?;
+        Some(SolveStep {
+            eliminations,
+            pattern: cells.to_vec(),
+            houses: vec![house],
+            ..SolveStep::new(technique)
+        })
+    }
+
+    /// Whether `num` can go in (row, col) without repeating a digit in its cage or making
+    /// the cage's sum unreachable. Always true for cells outside cages.
+    pub fn fits_cage(&self, row: usize, col: usize, num: u8) -> bool {
+        let Some(cage) = self.units.cage_of(row, col) else {
+            return true;
+        };
+        let cage = &self.cages()[cage];
+
+        let mut used = bit(num);
+        let mut total = num;
+        let mut empty = 0;
+        for &(r, c) in &cage.cells {
+            match self.grid[r][c] {
+                _ if (r, c) == (row, col) => {}
+                0 => empty += 1,
+                value if used & bit(value) != 0 => return false,
+                value => {
+                    used |= bit(value);
+                    total += value;
+                }
+            }
+        }
+        if total > cage.sum {
+            return false;
+        }
+
+        // The empty cells left need `empty` unused digits adding up to what remains
+        let remaining = cage.sum - total;
+        let unused: Vec<u8> = digits(ALL_DIGITS & !used).collect();
+        if empty > unused.len() {
+            return false;
+        }
+        let least: u8 = unused[..empty].iter().sum();
+        let most: u8 = unused[unused.len() - empty..].iter().sum();
+        (least..=most).contains(&remaining)
+    }
+
+    /// A cage repeating a digit, or whose placed digits and candidates can't make its sum.
+    pub fn find_cage_contradiction(&self) -> Option<Contradiction> {
+        for (index, cage) in self.cages().iter().enumerate() {
+            let mut seen: Mask = 0;
+            for &(r, c) in &cage.cells {
+                let digit = self.grid[r][c];
+                if digit == 0 {
+                    continue;
+                }
+                if seen & bit(digit) != 0 {
+                    return Some(Contradiction::DuplicateDigit {
+                        digit,
+                        house: House::Cage(index),
+                        cells: cage
+                            .cells
+                            .iter()
+                            .copied()
+                            .filter(|&(r, c)| self.grid[r][c] == digit)
+                            .collect(),
+                    });
+                }
+                seen |= bit(digit);
+            }
+
+            if sum_candidates(&self.cell_masks(&cage.cells), cage.sum).contains(&0) {
+                return Some(Contradiction::CageSum {
+                    sum: cage.sum,
+                    cells: cage.cells.clone(),
+                });
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,220 @@
+//! Puzzle metadata carried in header lines, e.g. `Grid 07 [hard] [killer] [author=Jane Doe]`
+
+use crate::prelude::*;
+
+use std::fmt;
+
+/// A puzzle's name and the optional tags that follow it in its header.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    pub name: String,
+    pub variation: Option<Variation>,
+    pub difficulty: Option<Difficulty>,
+    pub author: Option<String>,
+    pub source: Option<String>,
+    /// Boxes other than the usual ones for the grid's size, e.g. 2x3 on a 6x6 board.
+    pub boxes: Option<Shape>,
+}
+
+impl Metadata {
+    pub fn named(name: impl Into<String>) -> Metadata {
+        Metadata {
+            name: name.into(),
+            ..Metadata::default()
+        }
+    }
+
+    /// Parses a header: the name, then any number of `[tag]`s. A tag is a difficulty or
+    /// variation on its own, or `key=value` with key difficulty, variation, author, source or
+    /// boxes, the last written as width x height, e.g. `[boxes=2x3]`. Other bracketed text,
+    /// like `[page 3]`, stays part of the name unless in strict mode.
+    /// `column` is where `header` starts on line `line`, for error locations.
+    pub fn parse(
+        header: &str,
+        context: &ParseContext,
+        line: usize,
+        column: usize,
+    ) -> Result<Metadata> {
+        let name_end = header.find('[').unwrap_or(header.len());
+        let mut metadata = Metadata::named(header[..name_end].trim());
+        let mut rest = &header[name_end..];
+
+        while let Some(start) = rest.find('[') {
+            let tag_column = column - 1 + column_at(header, header.len() - rest.len() + start);
+            let unknown = |text: &str| -> Error {
+                ParseError::UnknownTag {
+                    at: context.at(line, tag_column),
+                    text: text.to_string(),
+                }
+                .into()
+            };
+
+            let Some(end) = rest[start..].find(']') else {
+                if context.strict {
+                    return Err(unknown(&rest[start..]));
+                }
+                metadata.name = format!("{} {}", metadata.name, &rest[start..]);
+                break;
+            };
+            let bracketed = &rest[start..=start + end];
+            let tag = &rest[start + 1..start + end];
+            rest = &rest[start + end + 1..];
+
+            let known = match tag.split_once('=') {
+                Some((key, value)) => {
+                    let value = value.trim();
+                    match key.trim() {
+                        "difficulty" => {
+                            value.parse().map(|d| metadata.difficulty = Some(d)).is_ok()
+                        }
+                        "variation" => value.parse().map(|v| metadata.variation = Some(v)).is_ok(),
+                        "author" => {
+                            metadata.author = Some(value.to_string());
+                            true
+                        }
+                        "source" => {
+                            metadata.source = Some(value.to_string());
+                            true
+                        }
+                        "boxes" => value.parse().map(|b| metadata.boxes = Some(b)).is_ok(),
+                        _ => false,
+                    }
+                }
+                None => {
+                    if let Ok(difficulty) = tag.parse() {
+                        metadata.difficulty = Some(difficulty);
+                        true
+                    } else if let Ok(variation) = tag.parse() {
+                        metadata.variation = Some(variation);
+                        true
+                    } else {
+                        false
+                    }
+                }
+            };
+            if !known {
+                if context.strict {
+                    return Err(unknown(tag));
+                }
+                metadata.name = format!("{} {}", metadata.name, bracketed);
+            }
+        }
+
+        Ok(metadata)
+    }
+}
+
+/// Writes the header back in the form `parse` reads.
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(difficulty) = self.difficulty {
+            write!(f, " [{}]", difficulty)?;
+        }
+        if let Some(variation) = self.variation {
+            write!(f, " [{}]", variation)?;
+        }
+        if let Some(author) = &self.author {
+            write!(f, " [author={}]", author)?;
+        }
+        if let Some(source) = &self.source {
+            write!(f, " [source={}]", source)?;
+        }
+        if let Some(boxes) = self.boxes {
+            write!(f, " [boxes={}]", boxes)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Difficulty {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "easy" => Ok(Difficulty::Easy),
+            "medium" => Ok(Difficulty::Medium),
+            "hard" => Ok(Difficulty::Hard),
+            "extreme" => Ok(Difficulty::Extreme),
+            _ => Err(format!("unknown difficulty '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Difficulty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+            Difficulty::Extreme => "extreme",
+        })
+    }
+}
+
+impl std::str::FromStr for Variation {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "standard" => Ok(Variation::Standard),
+            "sandwich" => Ok(Variation::Sandwhich),
+            "killer" => Ok(Variation::Killer),
+            "x-sudoku" | "diagonal" => Ok(Variation::XSudoku),
+            "jigsaw" | "irregular" => Ok(Variation::Jigsaw),
+            _ => Err(format!("unknown variation '{}'", s)),
+        }
+    }
+}
+
+impl fmt::Display for Variation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Variation::Standard => "standard",
+            Variation::Sandwhich => "sandwich",
+            Variation::Killer => "killer",
+            Variation::XSudoku => "x-sudoku",
+            Variation::Jigsaw => "jigsaw",
+        })
+    }
+}
+
+impl std::str::FromStr for Shape {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        let shape = s
+            .trim()
+            .to_ascii_lowercase()
+            .split_once('x')
+            .and_then(|(width, height)| Some(Shape::new(width.parse().ok()?, height.parse().ok()?)))
+            .filter(|shape| Shape::SIZES.contains(&shape.size()));
+        shape.ok_or_else(|| format!("unknown box shape '{}'", s))
+    }
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.box_width, self.box_height)
+    }
+}
+
+impl Puzzle {
+    /// The puzzle's metadata, with any difficulty its header gave, any non-standard variation
+    /// and any boxes that aren't the usual ones for its size.
+    pub fn metadata(&self) -> Metadata {
+        Metadata {
+            name: self.name.clone(),
+            variation: (self.variation != Variation::Standard).then_some(self.variation),
+            difficulty: self.difficulty,
+            author: self.author.clone(),
+            source: self.source.clone(),
+            boxes: Some(self.shape()).filter(|&shape| Shape::for_size(shape.size()) != Some(shape)),
+        }
+    }
+
+    /// The header line for this puzzle, e.g. `Grid 07 [hard] [killer]`.
+    pub fn header(&self) -> String {
+        self.metadata().to_string()
+    }
+}
@@ -1,13 +1,28 @@
-use std::collections::{HashMap, HashSet};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::prelude::*;
 use rand::prelude::IndexedRandom;
 use rand::seq::SliceRandom;
 
+pub mod batch;
+pub mod contradiction;
+pub mod dlx;
+pub mod export;
+pub mod format;
 pub mod import;
+pub mod jigsaw;
+pub mod killer;
+pub mod metadata;
+pub mod rate;
+pub mod report;
+pub mod sandwich;
 pub mod solve;
+pub mod strategy;
+pub mod trace;
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Difficulty {
     Easy,
     Medium,
@@ -15,94 +30,419 @@ pub enum Difficulty {
     Extreme,
 }
 
-#[derive(Clone)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Variation {
     Standard,
     Sandwhich,
     Killer,
+    XSudoku,
+    Jigsaw,
 }
 
 #[derive(Clone)]
 pub struct Puzzle {
     name: String,
     variation: Variation,
-    difficulty: Difficulty,
+    /// The header's difficulty; puzzles without one are rated when asked.
+    difficulty: Option<Difficulty>,
+    author: Option<String>,
+    source: Option<String>,
+    givens: Grid,
     board: SudokuBoard,
 }
 
+/// A square grid of digits, one row per `Vec`, with 0 for an empty cell.
+pub type Grid = Vec<Vec<u8>>;
+
+/// A set of digits, with digit `n` stored in bit `n - 1`.
+pub type Mask = u32;
+
+/// Mask with every digit 1-9 set, all the digits of a standard board.
+pub const ALL_DIGITS: Mask = 0x1ff;
+
+/// The boxes a board is split into, `box_width` cells across and `box_height` down. Rows,
+/// columns and boxes all hold `box_width * box_height` cells, which is also the largest digit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shape {
+    pub box_width: usize,
+    pub box_height: usize,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Shape::new(3, 3)
+    }
+}
+
+impl Shape {
+    /// Board sizes with a usual box shape.
+    pub const SIZES: [usize; 6] = [4, 6, 9, 12, 16, 25];
+
+    pub const fn new(box_width: usize, box_height: usize) -> Shape {
+        Shape {
+            box_width,
+            box_height,
+        }
+    }
+
+    /// The usual boxes for a board `size` cells across: square where possible, otherwise
+    /// wider than tall.
+    pub fn for_size(size: usize) -> Option<Shape> {
+        match size {
+            4 => Some(Shape::new(2, 2)),
+            6 => Some(Shape::new(3, 2)),
+            9 => Some(Shape::new(3, 3)),
+            12 => Some(Shape::new(4, 3)),
+            16 => Some(Shape::new(4, 4)),
+            25 => Some(Shape::new(5, 5)),
+            _ => None,
+        }
+    }
+
+    /// Cells in each row, column and box.
+    pub fn size(&self) -> usize {
+        self.box_width * self.box_height
+    }
+
+    /// Mask with every digit of the board set.
+    pub fn all_digits(&self) -> Mask {
+        Mask::MAX >> (Mask::BITS as usize - self.size())
+    }
+}
+
 #[derive(Clone)]
 pub struct SudokuBoard {
-    grid: [[u8; 9]; 9],
+    grid: Grid,
     method_counts: HashMap<String, usize>,
-    units: Units,
-    candidates: Vec<Vec<HashSet<u8>>>,
+    units: Arc<Units>,
+    candidates: Vec<Vec<Mask>>,
+    house_masks: Vec<Mask>, // Digits already placed in each house, indexed like `Units::houses`
+    trace: Vec<SolveStep>,
+    guesses: usize,    // Placements tried by the search backends
+    backtracks: usize, // Placements undone by the search backends
+}
+
+/// The board's box shape, and rules a variation adds to the rows, columns and boxes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Constraints {
+    pub shape: Shape,
+    pub cages: Vec<Cage>,
+    pub sandwiches: Sandwiches,
+    /// Both main diagonals are houses too, as in X-Sudoku.
+    pub diagonals: bool,
+    /// Jigsaw regions replacing the 3x3 boxes.
+    pub regions: Option<Regions>,
+}
+
+impl Constraints {
+    /// The variation these rules make a puzzle, for headers that don't say.
+    pub fn variation(&self) -> Variation {
+        if !self.cages.is_empty() {
+            Variation::Killer
+        } else if !self.sandwiches.is_empty() {
+            Variation::Sandwhich
+        } else if self.regions.is_some() {
+            Variation::Jigsaw
+        } else if self.diagonals {
+            Variation::XSudoku
+        } else {
+            Variation::Standard
+        }
+    }
+}
+
+/// A row, column or box (a jigsaw region in Jigsaw Sudoku), numbered from 0, an X-Sudoku diagonal (0 running from r1c1 to
+/// r9c9, 1 from r1c9 to r9c1), or a killer cage numbered like `Units::cages`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum House {
+    Row(usize),
+    Col(usize),
+    Box(usize),
+    Diagonal(usize),
+    Cage(usize),
 }
 
+/// The houses of the board (its rows, columns and boxes or jigsaw regions, plus the 2
+/// diagonals in X-Sudoku, each listing all of its cells), any killer cages and sandwich clues,
+/// and, for every cell, the houses containing it and the other cells that share a house or
+/// cage with it.
 #[derive(Clone)]
 pub struct Units {
-    row_units: Vec<HashSet<(usize, usize)>>,
-    col_units: Vec<HashSet<(usize, usize)>>,
-    box_units: Vec<HashSet<(usize, usize)>>,
+    shape: Shape,
+    houses: Vec<Vec<(usize, usize)>>,
+    labels: Vec<House>,
+    cell_houses: Vec<Vec<usize>>,
+    cages: Vec<Cage>,
+    cell_cages: Vec<Option<usize>>,
+    sandwiches: Sandwiches,
+    regions: Option<Regions>,
+    peers: Vec<Vec<(usize, usize)>>,
+    sees: Vec<bool>, // Peer lookup table, one entry per pair of cells
+}
+
+impl Units {
+    /// The box shape, which sets the board's size.
+    pub fn shape(&self) -> Shape {
+        self.shape
+    }
+
+    /// Cells in each row, column and box.
+    pub fn size(&self) -> usize {
+        self.shape.size()
+    }
+
+    /// Every cell in reading order.
+    pub fn all_cells(&self) -> impl Iterator<Item = (usize, usize)> {
+        let size = self.size();
+        (0..size * size).map(move |i| (i / size, i % size))
+    }
+
+    /// All houses: rows, then columns, then boxes, then any diagonals.
+    pub fn houses(&self) -> impl Iterator<Item = &Vec<(usize, usize)>> {
+        self.houses.iter()
+    }
+
+    /// Which row, column or box the house at `index` is.
+    pub fn label(&self, index: usize) -> House {
+        self.labels[index]
+    }
+
+    /// The cells of a house or cage.
+    pub fn cells(&self, house: House) -> &[(usize, usize)] {
+        if let House::Cage(cage) = house {
+            return &self.cages[cage].cells;
+        }
+        let index = self
+            .labels
+            .iter()
+            .position(|&label| label == house)
+            .unwrap();
+        &self.houses[index]
+    }
+
+    /// The box containing (row, col).
+    pub fn box_of(&self, row: usize, col: usize) -> usize {
+        self.cell_houses(row, col)
+            .iter()
+            .find_map(|&index| match self.labels[index] {
+                House::Box(b) => Some(b),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    /// Indices (into `houses`) of the houses containing (row, col).
+    pub fn cell_houses(&self, row: usize, col: usize) -> &[usize] {
+        &self.cell_houses[row * self.size() + col]
+    }
+
+    /// The killer cages, empty for other variations.
+    pub fn cages(&self) -> &[Cage] {
+        &self.cages
+    }
+
+    /// The sandwich clues, empty for other variations.
+    pub fn sandwiches(&self) -> &Sandwiches {
+        &self.sandwiches
+    }
+
+    /// The jigsaw regions, `None` when the boxes are the standard 3x3 ones.
+    pub fn regions(&self) -> Option<&Regions> {
+        self.regions.as_ref()
+    }
+
+    /// Index (into `cages`) of the cage containing (row, col), if any.
+    pub fn cage_of(&self, row: usize, col: usize) -> Option<usize> {
+        self.cell_cages[row * self.size() + col]
+    }
+
+    /// The cells sharing a house or cage with (row, col), not including the cell itself.
+    pub fn peers(&self, row: usize, col: usize) -> &[(usize, usize)] {
+        &self.peers[row * self.size() + col]
+    }
+
+    /// Whether two distinct cells share a house or cage.
+    pub fn sees(&self, a: (usize, usize), b: (usize, usize)) -> bool {
+        let size = self.size();
+        self.sees[(a.0 * size + a.1) * size * size + b.0 * size + b.1]
+    }
 }
 
 impl Puzzle {
+    /// A puzzle with the header's metadata. The variation follows from the constraints unless
+    /// tagged.
+    fn from_metadata(metadata: Metadata, grid: Grid, mut constraints: Constraints) -> Puzzle {
+        constraints.diagonals |= metadata.variation == Some(Variation::XSudoku);
+        let default_variation = constraints.variation();
+        Puzzle {
+            name: metadata.name,
+            variation: metadata.variation.unwrap_or(default_variation),
+            difficulty: metadata.difficulty,
+            author: metadata.author,
+            source: metadata.source,
+            givens: grid.clone(),
+            board: SudokuBoard::with_constraints(grid, constraints),
+        }
+    }
+
     pub fn print(&self) {
         println!("Puzzle: {}", self.name);
         self.board.print();
     }
 
-    pub fn solve(&mut self) -> bool {
+    pub fn solve(&mut self) -> SolveReport {
         self.board.solve()
     }
+
+    pub fn solve_with(&mut self, backend: Backend) -> SolveReport {
+        self.board.solve_with(backend)
+    }
+
+    pub fn solve_using(&mut self, solver: &Solver) -> SolveReport {
+        solver.solve(&mut self.board)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn variation(&self) -> Variation {
+        self.variation
+    }
+
+    /// The header's difficulty, or else the one the givens are rated at by the techniques
+    /// they need, however far the puzzle has been solved since. `None` for givens that
+    /// can't be solved, which have no difficulty.
+    pub fn difficulty(&self) -> Option<Difficulty> {
+        self.difficulty.or_else(|| {
+            let givens =
+                SudokuBoard::with_units(self.givens.clone(), Arc::clone(&self.board.units));
+            givens.rate().map(|rating| rating.difficulty)
+        })
+    }
+
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_deref()
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    /// The box shape, which sets the board's size.
+    pub fn shape(&self) -> Shape {
+        self.board.units.shape()
+    }
+
+    pub fn cages(&self) -> &[Cage] {
+        self.board.cages()
+    }
+
+    pub fn sandwiches(&self) -> &Sandwiches {
+        self.board.sandwiches()
+    }
+
+    pub fn regions(&self) -> Option<&Regions> {
+        self.board.regions()
+    }
+
+    pub fn board(&self) -> &SudokuBoard {
+        &self.board
+    }
+
+    pub fn count_solutions(&self, limit: usize) -> Vec<Grid> {
+        self.board.count_solutions(limit)
+    }
+
+    pub fn has_unique_solution(&self) -> bool {
+        self.board.has_unique_solution()
+    }
+
+    pub fn next_hint(&self) -> Option<SolveStep> {
+        self.board.next_hint()
+    }
+
+    pub fn rate(&self) -> Option<Rating> {
+        self.board.rate()
+    }
 }
 
 impl SudokuBoard {
     fn new() -> SudokuBoard {
-        let units: Units = Self::compute_units();
+        Self::from(vec![vec![0; 9]; 9]) // Initializes an empty board
+    }
 
-        let mut board = Self {
-            grid: [[0; 9]; 9], // Initializes an empty board
-            method_counts: HashMap::new(),
-            units,
-            candidates: vec![vec![HashSet::new(); 9]; 9],
-        };
-        board.compute_candidates();
-        board
+    fn from(grid: Grid) -> SudokuBoard {
+        Self::with_constraints(grid, Constraints::default())
     }
 
-    fn from(grid: [[u8; 9]; 9]) -> SudokuBoard {
-        let units = Self::compute_units();
+    /// A board with a variation's extra rules. The grid must have as many rows and columns
+    /// as the constraints' boxes have cells.
+    fn with_constraints(grid: Grid, constraints: Constraints) -> SudokuBoard {
+        thread_local! {
+            // Runs of puzzles with the same rules, like a file of standard puzzles, share units
+            static LAST_UNITS: RefCell<Option<(Constraints, Arc<Units>)>> = const { RefCell::new(None) };
+        }
+        let units = LAST_UNITS.with_borrow_mut(|last| match last {
+            Some((known, units)) if *known == constraints => Arc::clone(units),
+            _ => {
+                let units = Arc::new(Self::compute_units(constraints.clone()));
+                *last = Some((constraints, Arc::clone(&units)));
+                units
+            }
+        });
+        Self::with_units(grid, units)
+    }
+
+    /// A board sharing the houses and rules of another board of the same size.
+    fn with_units(grid: Grid, units: Arc<Units>) -> SudokuBoard {
+        let size = units.size();
         let mut board = Self {
-            grid, // Initializes an empty board
+            grid,
             method_counts: HashMap::new(),
             units,
-            candidates: vec![vec![HashSet::new(); 9]; 9],
+            candidates: vec![vec![0; size]; size],
+            house_masks: Vec::new(),
+            trace: Vec::new(),
+            guesses: 0,
+            backtracks: 0,
         };
         board.compute_candidates();
         board
     }
 
     pub fn print(&self) {
+        let Shape {
+            box_width,
+            box_height,
+        } = self.units.shape();
+        let size = self.size();
+        let border = |left: &str, middle: &str, right: &str| {
+            let segment = "─".repeat(box_width * 2 + 1);
+            let segments = vec![segment; size / box_width];
+            format!("{}{}{}", left, segments.join(middle), right)
+        };
+
         println!("Solved with {:?}", self.method_counts);
-        println!("┌───────┬───────┬───────┐");
+        println!("{}", border("┌", "┬", "┐"));
         for (i, row) in self.grid.iter().enumerate() {
             print!("│ ");
             for (j, &cell) in row.iter().enumerate() {
                 if cell == 0 {
                     print!("· ");
                 } else {
-                    print!("{} ", cell);
+                    print!("{} ", digit_char(cell));
                 }
-                if (j + 1) % 3 == 0 && j < 8 {
+                if (j + 1) % box_width == 0 && j + 1 < size {
                     print!("│ ");
                 }
             }
             println!("│");
-            if (i + 1) % 3 == 0 && i < 8 {
-                println!("├───────┼───────┼───────┤");
+            if (i + 1) % box_height == 0 && i + 1 < size {
+                println!("{}", border("├", "┼", "┤"));
             }
         }
-        println!("└───────┴───────┴───────┘");
+        println!("{}", border("└", "┴", "┘"));
     }
 
     fn increment_method_count(&mut self, method_name: &str) {
@@ -112,78 +452,166 @@ impl SudokuBoard {
             .or_insert(0) += 1;
     }
 
-    fn compute_units() -> Units {
-        let mut row_units = vec![HashSet::new(); 81];
-        let mut col_units = vec![HashSet::new(); 81];
-        let mut box_units = vec![HashSet::new(); 81];
+    fn compute_units(constraints: Constraints) -> Units {
+        let Constraints {
+            shape,
+            cages,
+            sandwiches,
+            diagonals,
+            regions,
+        } = constraints;
 
-        for row in 0..9 {
-            for col in 0..9 {
-                let index = row * 9 + col;
+        let size = shape.size();
+        let cells = size * size;
 
-                // Row
-                for c in 0..9 {
-                    if c != col {
-                        row_units[index].insert((row, c));
-                    }
-                }
+        let rows: Vec<Vec<(usize, usize)>> = (0..size)
+            .map(|row| (0..size).map(|col| (row, col)).collect())
+            .collect();
 
-                // Column
-                for r in 0..9 {
-                    if r != row {
-                        col_units[index].insert((r, col));
-                    }
-                }
+        let cols: Vec<Vec<(usize, usize)>> = (0..size)
+            .map(|col| (0..size).map(|row| (row, col)).collect())
+            .collect();
+
+        let layout = regions.clone().unwrap_or_else(|| Regions::boxes(shape));
+        let boxes: Vec<Vec<(usize, usize)>> = (0..size).map(|b| layout.cells(b)).collect();
+
+        let diagonals: Vec<Vec<(usize, usize)>> = match diagonals {
+            true => vec![
+                (0..size).map(|i| (i, i)).collect(),
+                (0..size).map(|i| (i, size - 1 - i)).collect(),
+            ],
+            false => Vec::new(),
+        };
+
+        let labels: Vec<House> = (0..size)
+            .map(House::Row)
+            .chain((0..size).map(House::Col))
+            .chain((0..size).map(House::Box))
+            .chain((0..diagonals.len()).map(House::Diagonal))
+            .collect();
+        let houses: Vec<Vec<(usize, usize)>> = rows
+            .into_iter()
+            .chain(cols)
+            .chain(boxes)
+            .chain(diagonals)
+            .collect();
 
-                // Box
-                let box_row = (row / 3) * 3;
-                let box_col = (col / 3) * 3;
-                for r in 0..3 {
-                    for c in 0..3 {
-                        let br = box_row + r;
-                        let bc = box_col + c;
-                        if br != row || bc != col {
-                            box_units[index].insert((br, bc));
-                        }
+        let mut cell_houses = vec![Vec::new(); cells];
+        for (index, house) in houses.iter().enumerate() {
+            for &(row, col) in house {
+                cell_houses[row * size + col].push(index);
+            }
+        }
+
+        let mut cell_cages = vec![None; cells];
+        for (index, cage) in cages.iter().enumerate() {
+            for &(row, col) in &cage.cells {
+                cell_cages[row * size + col] = Some(index);
+            }
+        }
+
+        // Digits can't repeat in a cage either, so cage mates are peers too
+        let mut peers = vec![Vec::new(); cells];
+        for house in houses.iter().chain(cages.iter().map(|cage| &cage.cells)) {
+            for &(row, col) in house {
+                let cell_peers = &mut peers[row * size + col];
+                for &cell in house {
+                    if cell != (row, col) && !cell_peers.contains(&cell) {
+                        cell_peers.push(cell);
                     }
                 }
             }
         }
 
+        let mut sees = vec![false; cells * cells];
+        for (index, cell_peers) in peers.iter().enumerate() {
+            for &(row, col) in cell_peers {
+                sees[index * cells + row * size + col] = true;
+            }
+        }
+
         Units {
-            row_units,
-            col_units,
-            box_units,
+            shape,
+            houses,
+            labels,
+            cell_houses,
+            cages,
+            cell_cages,
+            sandwiches,
+            regions,
+            peers,
+            sees,
         }
     }
 
     fn compute_candidates(&mut self) {
-        self.candidates = vec![vec![HashSet::new(); 9]; 9];
+        self.house_masks = self
+            .units
+            .houses()
+            .map(|house| {
+                house
+                    .iter()
+                    .filter(|&&(r, c)| self.grid[r][c] != 0)
+                    .fold(0, |mask, &(r, c)| mask | bit(self.grid[r][c]))
+            })
+            .collect();
 
-        for row in 0..9 {
-            for col in 0..9 {
-                if self.grid[row][col] == 0 {
-                    let mut possible = (1..=9).collect::<HashSet<_>>();
-                    let index = row * 9 + col;
+        let all_digits = self.units.shape().all_digits();
+        for row in 0..self.size() {
+            for col in 0..self.size() {
+                self.candidates[row][col] = if self.grid[row][col] == 0 {
+                    // Remove numbers already present in any house containing the cell
+                    all_digits & !self.placed_digits(row, col)
+                } else {
+                    0
+                };
+            }
+        }
+    }
 
-                    // Remove numbers already present in row
-                    for &(r, c) in &self.units.row_units[index] {
-                        possible.remove(&self.grid[r][c]);
-                    }
+    /// Cells in each row, column and box: 9 on a standard board.
+    pub fn size(&self) -> usize {
+        self.units.size()
+    }
 
-                    // Remove numbers already present in column
-                    for &(r, c) in &self.units.col_units[index] {
-                        possible.remove(&self.grid[r][c]);
-                    }
+    /// Whether cages or sandwich clues constrain sums, which exact cover can't express.
+    pub fn has_sum_rules(&self) -> bool {
+        !self.units.cages.is_empty() || !self.units.sandwiches.is_empty()
+    }
 
-                    // Remove numbers already present in box
-                    for &(r, c) in &self.units.box_units[index] {
-                        possible.remove(&self.grid[r][c]);
-                    }
+    /// The digits each cell may hold: its value if placed, otherwise its candidates.
+    fn cell_masks(&self, cells: &[(usize, usize)]) -> Vec<Mask> {
+        cells
+            .iter()
+            .map(|&(r, c)| match self.grid[r][c] {
+                0 => self.candidates[r][c],
+                value => bit(value),
+            })
+            .collect()
+    }
 
-                    self.candidates[row][col] = possible;
-                }
-            }
+    /// Digits already placed in any house containing (row, col).
+    fn placed_digits(&self, row: usize, col: usize) -> Mask {
+        self.units
+            .cell_houses(row, col)
+            .iter()
+            .fold(0, |mask, &house| mask | self.house_masks[house])
+    }
+
+    /// Writes `value` into the grid and marks it as placed in the cell's houses.
+    fn place(&mut self, row: usize, col: usize, value: u8) {
+        self.grid[row][col] = value;
+        for &house in self.units.cell_houses(row, col) {
+            self.house_masks[house] |= bit(value);
+        }
+    }
+
+    /// Clears (row, col) and unmarks its value in the cell's houses.
+    fn unplace(&mut self, row: usize, col: usize) {
+        let value = self.grid[row][col];
+        self.grid[row][col] = 0;
+        for &house in self.units.cell_houses(row, col) {
+            self.house_masks[house] &= !bit(value);
         }
     }
 }
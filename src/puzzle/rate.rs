@@ -0,0 +1,81 @@
+//! Difficulty rating from the techniques a logical solve needs
+
+use crate::prelude::*;
+
+/// Score given to a puzzle that logic alone can't finish.
+pub const SEARCH_RATING: f32 = 10.0;
+
+/// How hard a puzzle is to solve by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rating {
+    /// Weight of the hardest strategy needed, from 1.5 up to `SEARCH_RATING`.
+    pub score: f32,
+    /// Name of the hardest strategy needed, or "search" when logic stalls.
+    pub hardest: &'static str,
+    pub difficulty: Difficulty,
+}
+
+impl Difficulty {
+    /// Maps a rating score to a difficulty: singles are Easy, intersections, subsets and
+    /// small fish Medium, wings and larger patterns Hard, and anything needing search Extreme.
+    pub fn from_score(score: f32) -> Difficulty {
+        match score {
+            s if s < 2.5 => Difficulty::Easy,
+            s if s < 4.2 => Difficulty::Medium,
+            s if s < SEARCH_RATING => Difficulty::Hard,
+            _ => Difficulty::Extreme,
+        }
+    }
+}
+
+impl Solver {
+    /// Rates the board by solving a copy with this solver's strategies, simplest step first,
+    /// scoring it by the weight of the hardest strategy needed. A board that breaks a rule,
+    /// at the start or after any step, or that has no solution, isn't rated.
+    pub fn rate(&self, board: &SudokuBoard) -> Option<Rating> {
+        let mut board = board.clone();
+        let mut score = 0.0;
+        let mut hardest = "none";
+
+        loop {
+            if board.find_contradiction().is_some() {
+                return None;
+            }
+            if board.is_solved() {
+                break;
+            }
+            let Some(strategy) = self
+                .strategies()
+                .find(|strategy| strategy.apply(&mut board).is_some())
+            else {
+                // Logic stalled, which only makes the puzzle Extreme if a search can finish it
+                if board.count_solutions(1).is_empty() {
+                    return None;
+                }
+                return Some(Rating {
+                    score: SEARCH_RATING,
+                    hardest: "search",
+                    difficulty: Difficulty::Extreme,
+                });
+            };
+
+            if strategy.difficulty() > score {
+                score = strategy.difficulty();
+                hardest = strategy.name();
+            }
+        }
+
+        Some(Rating {
+            score,
+            hardest,
+            difficulty: Difficulty::from_score(score),
+        })
+    }
+}
+
+impl SudokuBoard {
+    /// Rates the board with every built-in technique, or `None` if it can't be solved.
+    pub fn rate(&self) -> Option<Rating> {
+        Solver::new().rate(self)
+    }
+}
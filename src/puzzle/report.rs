@@ -0,0 +1,94 @@
+//! Structured result of a solve
+
+use crate::prelude::*;
+
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// What finished the solve: logic alone, or one of the search backends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SolveMethod {
+    Logical,
+    Backtracking,
+    Dlx,
+}
+
+impl From<Backend> for SolveMethod {
+    fn from(backend: Backend) -> Self {
+        match backend {
+            Backend::Backtracking => SolveMethod::Backtracking,
+            Backend::Dlx => SolveMethod::Dlx,
+        }
+    }
+}
+
+/// Everything a solve produced, for callers that aggregate results instead of reading output.
+#[derive(Clone, Debug)]
+pub struct SolveReport {
+    pub outcome: SolveOutcome,
+    /// The last stage that ran; `Logical` if the search backend was never needed.
+    pub method: SolveMethod,
+    pub elapsed: Duration,
+    /// Steps applied per technique.
+    pub method_counts: HashMap<String, usize>,
+    /// Placements tried by the search backend.
+    pub guesses: usize,
+    /// Placements the search backend had to undo.
+    pub backtracks: usize,
+    /// The grid as the solve left it.
+    pub grid: Grid,
+}
+
+impl SolveReport {
+    pub fn is_solved(&self) -> bool {
+        self.outcome.is_solved()
+    }
+
+    pub fn elapsed_ms(&self) -> f64 {
+        self.elapsed.as_secs_f64() * 1000.0
+    }
+}
+
+impl fmt::Display for SolveReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let method = match self.method {
+            SolveMethod::Logical => "logically",
+            SolveMethod::Backtracking => "with brute force",
+            SolveMethod::Dlx => "with dancing links",
+        };
+        match &self.outcome {
+            SolveOutcome::Solved => write!(f, "solved {}", method)?,
+            outcome => write!(f, "{}", outcome)?,
+        }
+        write!(f, " in {:.4} ms", self.elapsed_ms())?;
+        if self.method != SolveMethod::Logical {
+            write!(
+                f,
+                " ({} guesses, {} backtracks)",
+                self.guesses, self.backtracks
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SudokuBoard {
+    /// Snapshot of the board's counts and grid as a report.
+    pub fn report(
+        &self,
+        outcome: SolveOutcome,
+        method: SolveMethod,
+        elapsed: Duration,
+    ) -> SolveReport {
+        SolveReport {
+            outcome,
+            method,
+            elapsed,
+            method_counts: self.method_counts.clone(),
+            guesses: self.guesses,
+            backtracks: self.backtracks,
+            grid: self.grid.clone(),
+        }
+    }
+}
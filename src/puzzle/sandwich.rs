@@ -0,0 +1,274 @@
+//! Sandwich Sudoku clues: the sum of the digits lying between the 1 and the 9 of a row or column
+
+use crate::prelude::*;
+
+use std::fmt;
+
+/// The 1 and 9 that form the crusts of a sandwich.
+const CRUSTS: Mask = 0x101;
+
+/// Largest possible sandwich, 2 + 3 + ... + 8.
+const MAX_SANDWICH: u8 = 35;
+
+/// Sandwich clues outside the grid, `None` for a row or column without one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sandwiches {
+    pub rows: [Option<u8>; 9],
+    pub cols: [Option<u8>; 9],
+}
+
+impl Sandwiches {
+    pub fn is_empty(&self) -> bool {
+        self.rows.iter().chain(&self.cols).all(Option::is_none)
+    }
+
+    /// Every clued row and column with its clue, rows first.
+    pub fn clues(&self) -> impl Iterator<Item = (House, u8)> + '_ {
+        let rows = self
+            .rows
+            .iter()
+            .enumerate()
+            .map(|(i, &clue)| (House::Row(i), clue));
+        let cols = self
+            .cols
+            .iter()
+            .enumerate()
+            .map(|(i, &clue)| (House::Col(i), clue));
+        rows.chain(cols)
+            .filter_map(|(house, clue)| clue.map(|clue| (house, clue)))
+    }
+
+    /// Why no sandwich of different digits from 2 to 8 adds up to `clue`, if none does, as
+    /// for 1 or 34.
+    pub fn check_clue(clue: u8) -> core::result::Result<(), String> {
+        let fillings = ALL_DIGITS & !CRUSTS;
+        let reachable = clue <= MAX_SANDWICH
+            && (0..=7)
+                .flat_map(|size| subsets(fillings, size))
+                .any(|sandwich| digits(sandwich).sum::<u8>() == clue);
+        if !reachable {
+            return Err("can't be made from different digits 2 to 8".to_string());
+        }
+        Ok(())
+    }
+
+    /// Reads the clue lines: `columns` and `rows`, in either order, each followed by 9 clues
+    /// separated by spaces, with `.` for no clue, e.g. `rows 10 . 5 0 35 . . 2 9`. `line` is
+    /// the line number of the first.
+    pub fn parse(lines: &[String], context: &ParseContext, line: usize) -> Result<Sandwiches> {
+        let mut sandwiches = Sandwiches::default();
+        let mut seen = Vec::new();
+        for (offset, text) in lines.iter().enumerate() {
+            let invalid = |token: &str, reason: &str| -> Error {
+                let column = column_at(text, token.as_ptr() as usize - text.as_ptr() as usize);
+                ParseError::InvalidClue {
+                    at: context.at(line + offset, column),
+                    text: token.to_string(),
+                    reason: reason.to_string(),
+                }
+                .into()
+            };
+
+            let mut tokens = text.split_whitespace();
+            let label = tokens.next().unwrap_or(text.as_str());
+            let clues = match label {
+                "rows" => &mut sandwiches.rows,
+                "columns" => &mut sandwiches.cols,
+                _ => return Err(invalid(label, "should start with rows or columns")),
+            };
+            if seen.contains(&label) {
+                return Err(invalid(label, "is given twice"));
+            }
+            seen.push(label);
+
+            let tokens: Vec<&str> = tokens.collect();
+            if tokens.len() != 9 {
+                return Err(invalid(
+                    label,
+                    &format!("has {} clues instead of 9", tokens.len()),
+                ));
+            }
+            for (clue, token) in clues.iter_mut().zip(tokens) {
+                if token == "." {
+                    continue;
+                }
+                let sum: u8 = token
+                    .parse()
+                    .map_err(|_| invalid(token, "isn't a number or '.'"))?;
+                Self::check_clue(sum).map_err(|reason| invalid(token, &reason))?;
+                *clue = Some(sum);
+            }
+        }
+        Ok(sandwiches)
+    }
+
+    /// The clue lines `parse` reads, columns first.
+    pub fn to_lines(&self) -> Vec<String> {
+        vec![
+            format!("columns {}", clue_list(&self.cols)),
+            format!("rows {}", clue_list(&self.rows)),
+        ]
+    }
+}
+
+impl fmt::Display for Sandwiches {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_lines().join("\n"))
+    }
+}
+
+fn clue_list(clues: &[Option<u8>; 9]) -> String {
+    clues
+        .iter()
+        .map(|clue| clue.map_or(".".to_string(), |sum| sum.to_string()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Given the digits each cell of a line may hold, the digits each cell can take when the
+/// digits between the line's 1 and 9 add up to `sum`. `None` if no placement of the 1 and 9
+/// works.
+pub fn sandwich_candidates(masks: &[Mask], sum: u8) -> Option<Vec<Mask>> {
+    let mut allowed = vec![0; masks.len()];
+    let mut possible = false;
+
+    for one in digits_at(masks, 1) {
+        for nine in digits_at(masks, 9) {
+            if one == nine {
+                continue;
+            }
+            let (start, end) = (one.min(nine), one.max(nine));
+
+            // The filling uses digits 2-8, and so does everything outside it
+            let between: Vec<Mask> = masks[start + 1..end].iter().map(|m| m & !CRUSTS).collect();
+            let filling = match between.is_empty() {
+                true if sum == 0 => Vec::new(),
+                true => continue,
+                false => sum_candidates(&between, sum),
+            };
+            let outside = (0..masks.len()).filter(|&i| i < start || i > end);
+            if filling.contains(&0) || outside.clone().any(|i| masks[i] & !CRUSTS == 0) {
+                continue;
+            }
+
+            possible = true;
+            allowed[one] |= bit(1);
+            allowed[nine] |= bit(9);
+            for (i, mask) in filling.into_iter().enumerate() {
+                allowed[start + 1 + i] |= mask;
+            }
+            for i in outside {
+                allowed[i] |= masks[i] & !CRUSTS;
+            }
+        }
+    }
+    possible.then_some(allowed)
+}
+
+/// Positions in `masks` that can hold `num`.
+fn digits_at(masks: &[Mask], num: u8) -> impl Iterator<Item = usize> + '_ {
+    (0..masks.len()).filter(move |&i| masks[i] & bit(num) != 0)
+}
+
+impl SudokuBoard {
+    /// The sandwich clues, empty for other variations.
+    pub fn sandwiches(&self) -> &Sandwiches {
+        self.units.sandwiches()
+    }
+
+    /// Sandwich: A candidate that fits no placement of its line's 1 and 9 whose filling adds
+    /// up to the clue, including a 1 or 9 that can't be a crust and a digit that would have
+    /// to sit between them.
+    pub fn find_sandwich(&self) -> Option<SolveStep> {
+        for (house, sum) in self.sandwiches().clues() {
+            let cells = self.units.cells(house);
+            let Some(allowed) = sandwich_candidates(&self.cell_masks(cells), sum) else {
+                continue;
+            };
+
+            let eliminations: Vec<(usize, usize, u8)> = cells
+                .iter()
+                .zip(allowed)
+                .filter(|&(&(r, c), _)| self.grid[r][c] == 0)
+                .flat_map(|(&(r, c), allowed)| {
+                    digits(self.candidates[r][c] & !allowed).map(move |num| (r, c, num))
+                })
+                .collect();
+            if !eliminations.is_empty() {
+                return Some(SolveStep {
+                    eliminations,
+                    pattern: cells.to_vec(),
+                    houses: vec![house],
+                    ..SolveStep::new("sandwich_sums")
+                });
+            }
+        }
+        None
+    }
+
+    /// Whether `num` can go in (row, col) without completing a sandwich with the wrong sum or
+    /// overfilling one. Always true for lines without a clue.
+    pub fn fits_sandwich(&self, row: usize, col: usize, num: u8) -> bool {
+        // Sandwich clues only exist on 9x9 boards
+        if self.sandwiches().is_empty() {
+            return true;
+        }
+        let clues = [
+            (self.sandwiches().rows[row], House::Row(row)),
+            (self.sandwiches().cols[col], House::Col(col)),
+        ];
+        clues.into_iter().all(|(clue, house)| {
+            let Some(sum) = clue else {
+                return true;
+            };
+            let values: Vec<u8> = self
+                .units
+                .cells(house)
+                .iter()
+                .map(|&(r, c)| {
+                    if (r, c) == (row, col) {
+                        num
+                    } else {
+                        self.grid[r][c]
+                    }
+                })
+                .collect();
+            let (Some(one), Some(nine)) = (
+                values.iter().position(|&v| v == 1),
+                values.iter().position(|&v| v == 9),
+            ) else {
+                return true;
+            };
+
+            let filling = &values[one.min(nine) + 1..one.max(nine)];
+            let total: u32 = filling.iter().map(|&v| v as u32).sum();
+            match filling.contains(&0) {
+                true => total <= sum as u32,
+                false => total == sum as u32,
+            }
+        })
+    }
+
+    /// A clued row or column whose 1 and 9 can't be placed around a filling of the right sum.
+    pub fn find_sandwich_contradiction(&self) -> Option<Contradiction> {
+        self.sandwiches().clues().find_map(|(house, sum)| {
+            let masks = self.cell_masks(self.units.cells(house));
+            sandwich_candidates(&masks, sum)
+                .is_none()
+                .then_some(Contradiction::SandwichSum { house, sum })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clues_are_sums_of_different_digits_2_to_8() {
+        let unreachable: Vec<u8> = (0..=40)
+            .filter(|&clue| Sandwiches::check_clue(clue).is_err())
+            .collect();
+        assert_eq!(unreachable, [1, 34, 36, 37, 38, 39, 40]);
+    }
+}
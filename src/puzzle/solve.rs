@@ -1,574 +1,1194 @@
 use crate::prelude::*;
 
-use std::collections::HashSet;
-use std::time::Instant;
+use std::sync::Arc;
+
+/// Search used once the logical techniques stop making progress.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// Most-constrained-cell backtracking (`brute_force`).
+    #[default]
+    Backtracking,
+    /// Dancing Links exact cover search (`solve_dlx`).
+    Dlx,
+}
+
+impl Backend {
+    /// The backend for boards with `size` rows when none was chosen. Backtracking is quick on
+    /// 9x9 boards but can take minutes on bigger ones, where Dancing Links stays fast.
+    pub fn for_size(size: usize) -> Backend {
+        if size > 9 {
+            Backend::Dlx
+        } else {
+            Backend::Backtracking
+        }
+    }
+}
+
+impl std::str::FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> core::result::Result<Self, Self::Err> {
+        match s {
+            "backtracking" => Ok(Backend::Backtracking),
+            "dlx" => Ok(Backend::Dlx),
+            _ => Err(format!(
+                "unknown backend '{}', expected backtracking or dlx",
+                s
+            )),
+        }
+    }
+}
 
 impl SudokuBoard {
-    pub fn solve(&mut self) -> bool {
-        let start_time = Instant::now();
-        loop {
-            let mut progress = false;
-            progress |= self.fill_single_candidates();
-            progress |= self.naked_pairs();
-            progress |= self.naked_triples();
-            // progress |= self.hidden_pairs();
-            // progress |= self.hidden_triples();
-            progress |= self.x_wing();
-            // progress |= self.y_wing();
-            if self.is_solved() {
-                let elapsed_time = start_time.elapsed();
-
-                println!(
-                    "Solved logically in {:.4} ms",
-                    elapsed_time.as_secs_f64() * 1000.0
-                );
+    pub fn solve(&mut self) -> SolveReport {
+        Solver::new().solve(self)
+    }
+
+    /// Solves with every built-in technique, then finishes with the given search backend.
+    pub fn solve_with(&mut self, backend: Backend) -> SolveReport {
+        Solver::new().backend(backend).solve(self)
+    }
+
+    /// Backtracking search that fills the grid with the first solution found, always guessing
+    /// in the empty cell with the fewest valid candidates.
+    pub fn brute_force(&mut self) -> bool {
+        let Some(((row, col), options)) = self.most_constrained_cell() else {
+            return true;
+        };
+        for num in options {
+            self.place(row, col, num);
+            self.guesses += 1;
+            if self.brute_force() {
                 return true;
             }
-            if !progress {
-                break;
-            }
+            self.unplace(row, col);
+            self.backtracks += 1;
         }
-        let success = self.brute_force();
+        false
+    }
 
-        let elapsed_time = start_time.elapsed();
-        if success {
-            println!(
-                "Solved with brute force in {:.4} ms",
-                elapsed_time.as_secs_f64() * 1000.0
-            );
+    /// Searches for up to `limit` solutions of the current grid and returns the ones found,
+    /// so the count is capped at `limit`. The board itself is left untouched.
+    pub fn count_solutions(&self, limit: usize) -> Vec<Grid> {
+        if self.has_sum_rules() {
+            self.search_solutions(limit)
         } else {
-            println!(
-                "Could not solve in {:.4} ms",
-                elapsed_time.as_secs_f64() * 1000.0
-            );
+            self.dlx_solutions(limit)
         }
-        success
     }
 
-    pub fn brute_force(&mut self) -> bool {
-        if let Some((row, col)) = self.find_empty() {
-            for num in 1..=9 {
-                if self.is_valid(row, col, num) {
-                    self.grid[row][col] = num;
-                    if self.brute_force() {
-                        return true;
-                    }
-                    self.grid[row][col] = 0
+    /// Backtracking search for up to `limit` solutions, trying the empty cell with the fewest
+    /// valid candidates first. The board itself is left untouched.
+    pub fn search_solutions(&self, limit: usize) -> Vec<Grid> {
+        // Logical steps never rule out a solution, and leave far fewer cells to search
+        let mut board = self.clone();
+        let solver = Solver::new();
+        while board.find_contradiction().is_none() && solver.step(&mut board).is_some() {}
+        if board.find_contradiction().is_some() {
+            return Vec::new();
+        }
+
+        let mut solutions = Vec::new();
+        if limit > 0 {
+            board.search(limit, &mut solutions);
+        }
+        solutions
+    }
+
+    fn search(&mut self, limit: usize, solutions: &mut Vec<Grid>) {
+        let Some(((row, col), options)) = self.most_constrained_cell() else {
+            solutions.push(self.grid.clone());
+            return;
+        };
+        for num in options {
+            self.place(row, col, num);
+            self.search(limit, solutions);
+            self.unplace(row, col);
+            if solutions.len() >= limit {
+                return;
+            }
+        }
+    }
+
+    /// The empty cell with the fewest digits that are both candidates and valid there, along
+    /// with those digits. `None` once the grid is full.
+    fn most_constrained_cell(&self) -> Option<((usize, usize), Vec<u8>)> {
+        let mut best: Option<((usize, usize), Vec<u8>)> = None;
+        for (row, col) in self.units.all_cells() {
+            if self.grid[row][col] != 0 {
+                continue;
+            }
+            let options: Vec<u8> = digits(self.candidates[row][col])
+                .filter(|&num| self.is_valid(row, col, num))
+                .collect();
+            if best
+                .as_ref()
+                .is_none_or(|(_, fewest)| options.len() < fewest.len())
+            {
+                let done = options.len() <= 1;
+                best = Some(((row, col), options));
+                if done {
+                    break;
                 }
             }
-            false
-        } else {
-            true
         }
+        best
     }
 
-    pub fn fill_single_candidates(&mut self) -> bool {
+    /// Whether the grid has exactly one solution. Stops searching after the second.
+    pub fn has_unique_solution(&self) -> bool {
+        self.count_solutions(2).len() == 1
+    }
+
+    /// The simplest step that applies to the current position, leaving the board untouched.
+    /// When no technique applies, reveals the most constrained empty cell from the unique
+    /// solution instead. Returns `None` if the board is solved or has no unique solution.
+    pub fn next_hint(&self) -> Option<SolveStep> {
+        if self.is_solved() {
+            return None;
+        }
+        let solutions = self.count_solutions(2);
+        let [solution] = &solutions[..] else {
+            return None;
+        };
+
+        if let Some(step) = TECHNIQUES
+            .iter()
+            .find_map(|technique| (technique.find)(self))
+        {
+            return Some(step);
+        }
+        let (row, col) = self
+            .units
+            .all_cells()
+            .filter(|&(r, c)| self.grid[r][c] == 0)
+            .min_by_key(|&(r, c)| self.candidates[r][c].count_ones())?;
+
+        Some(SolveStep {
+            placements: vec![(row, col, solution[row][col])],
+            pattern: vec![(row, col)],
+            ..SolveStep::new("solution")
+        })
+    }
+
+    /// Applies steps from `find` until it finds no more, returning whether any were applied.
+    fn apply_all(&mut self, find: impl Fn(&Self) -> Option<SolveStep>) -> bool {
         let mut changed = false;
-        loop {
-            let mut progress = false;
-            for row in 0..9 {
-                for col in 0..9 {
-                    if self.grid[row][col] == 0 && self.candidates[row][col].len() == 1 {
-                        let value = *self.candidates[row][col].iter().next().unwrap();
-                        self.set_value(row, col, value);
-                        progress = true;
-                    }
+        while let Some(step) = find(self) {
+            self.apply_step(step);
+            changed = true;
+        }
+        changed
+    }
+
+    /// Performs a step's placements and eliminations, and records it in the trace and `method_counts`.
+    pub fn apply_step(&mut self, step: SolveStep) {
+        for &(row, col, num) in &step.placements {
+            self.set_value(row, col, num);
+        }
+        for &(row, col, num) in &step.eliminations {
+            self.remove_candidate(row, col, num);
+        }
+        self.increment_method_count(step.technique);
+        self.trace.push(step);
+    }
+
+    pub fn fill_single_candidates(&mut self) -> bool {
+        self.apply_all(Self::find_naked_single)
+    }
+
+    /// Naked Single: An empty cell with only one candidate left.
+    pub fn find_naked_single(&self) -> Option<SolveStep> {
+        for row in 0..self.size() {
+            for col in 0..self.size() {
+                let mask = self.candidates[row][col];
+                if self.grid[row][col] == 0 && mask.count_ones() == 1 {
+                    let num = mask.trailing_zeros() as u8 + 1;
+                    return Some(SolveStep {
+                        placements: vec![(row, col, num)],
+                        pattern: vec![(row, col)],
+                        ..SolveStep::new("naked_singles")
+                    });
                 }
             }
+        }
+        None
+    }
+
+    pub fn hidden_singles(&mut self) -> bool {
+        self.apply_all(Self::find_hidden_single)
+    }
 
-            if !progress {
-                break;
+    /// Hidden Single: A digit that has only one possible cell within a row, column, or box.
+    /// Places the digit in that cell even if the cell still has other candidates.
+    pub fn find_hidden_single(&self) -> Option<SolveStep> {
+        for (index, house) in self.units.houses().enumerate() {
+            for num in 1..=self.size() as u8 {
+                if self.house_masks[index] & bit(num) != 0 {
+                    continue;
+                }
+
+                let mut cells = house
+                    .iter()
+                    .filter(|&&(r, c)| self.candidates[r][c] & bit(num) != 0);
+
+                if let (Some(&(row, col)), None) = (cells.next(), cells.next()) {
+                    return Some(SolveStep {
+                        placements: vec![(row, col, num)],
+                        pattern: vec![(row, col)],
+                        digits: vec![num],
+                        houses: vec![self.units.label(index)],
+                        ..SolveStep::new("hidden_singles")
+                    });
+                }
             }
-            changed = true;
         }
+        None
+    }
 
-        changed
+    pub fn pointing_pairs(&mut self) -> bool {
+        self.apply_all(Self::find_pointing_pair)
     }
 
-    /// Naked Pairs: Finds two cells in a row, column, or box that have the same two candidates.
-    /// Removes those candidates from other cells in the same unit.
-    pub fn naked_pairs(&mut self) -> bool {
-        let mut changed = false;
+    /// Pointing Pairs/Triples: If a digit's candidates within a box all lie in one row or column,
+    /// the digit must go in that box, so it is removed from the rest of that row or column.
+    pub fn find_pointing_pair(&self) -> Option<SolveStep> {
+        for b in 0..self.size() {
+            for num in 1..=self.size() as u8 {
+                let cells = self.cells_with_candidate(self.units.cells(House::Box(b)), num);
+                if cells.len() < 2 {
+                    continue;
+                }
 
-        // Iterate over all units (rows, columns, and boxes)
-        for units in [
-            &self.units.row_units,
-            &self.units.col_units,
-            &self.units.box_units,
-        ] {
-            for unit in units.iter() {
-                // Collect cells with exactly two candidates
-                let mut candidate_cells = Vec::new();
-                for &(row, col) in unit {
-                    if self.grid[row][col] == 0 && self.candidates[row][col].len() == 2 {
-                        candidate_cells.push((row, col));
+                let (row, col) = cells[0];
+                let lines = [
+                    (cells.iter().all(|&(r, _)| r == row), House::Row(row)),
+                    (cells.iter().all(|&(_, c)| c == col), House::Col(col)),
+                ];
+                for (aligned, line) in lines {
+                    if !aligned {
+                        continue;
                     }
-                }
 
-                // Check for naked pairs
-                for i in 0..candidate_cells.len() {
-                    for j in i + 1..candidate_cells.len() {
-                        let (row1, col1) = candidate_cells[i];
-                        let (row2, col2) = candidate_cells[j];
-
-                        // Ensure we are comparing two distinct cells
-                        if (row1, col1) != (row2, col2) {
-                            // Check if the two cells have the same candidates
-                            if self.candidates[row1][col1] == self.candidates[row2][col2] {
-                                let pair_values = self.candidates[row1][col1].clone();
-
-                                // Remove the pair values from other cells in the unit
-                                for &(r, c) in unit {
-                                    if (r, c) != (row1, col1) && (r, c) != (row2, col2) {
-                                        for &num in &pair_values {
-                                            if self.candidates[r][c].remove(&num) {
-                                                changed = true;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                    let eliminations: Vec<(usize, usize, u8)> = self
+                        .cells_with_candidate(self.units.cells(line), num)
+                        .into_iter()
+                        .filter(|cell| !cells.contains(cell))
+                        .map(|(r, c)| (r, c, num))
+                        .collect();
+                    if !eliminations.is_empty() {
+                        return Some(SolveStep {
+                            eliminations,
+                            pattern: cells,
+                            digits: vec![num],
+                            houses: vec![House::Box(b), line],
+                            ..SolveStep::new("pointing_pairs")
+                        });
                     }
                 }
             }
         }
+        None
+    }
 
-        if changed {
-            self.increment_method_count("naked_pairs");
-        }
-        changed
+    pub fn box_line_reduction(&mut self) -> bool {
+        self.apply_all(Self::find_box_line_reduction)
     }
 
-    /// Naked Triples: Same as Naked Pairs but with 3 cells.
-    pub fn naked_triples(&mut self) -> bool {
-        let mut changed = false;
+    /// Box/Line Reduction (Claiming): If a digit's candidates within a row or column all lie in one box,
+    /// the digit must go in that line, so it is removed from the rest of that box.
+    pub fn find_box_line_reduction(&self) -> Option<SolveStep> {
+        let size = self.size();
+        for line in (0..size).map(House::Row).chain((0..size).map(House::Col)) {
+            for num in 1..=self.size() as u8 {
+                let cells = self.cells_with_candidate(self.units.cells(line), num);
+                if cells.len() < 2 {
+                    continue;
+                }
 
-        for units in [
-            &self.units.row_units,
-            &self.units.col_units,
-            &self.units.box_units,
-        ] {
-            for unit in units.iter() {
-                let mut candidates = Vec::new();
-
-                for &(row, col) in unit {
-                    if self.grid[row][col] == 0 {
-                        let possible = &self.candidates[row][col]; // Immutable borrow
-                        if possible.len() == 3 {
-                            candidates.push((row, col));
-                        }
-                    }
+                let b = self.units.box_of(cells[0].0, cells[0].1);
+                if !cells.iter().all(|&(r, c)| self.units.box_of(r, c) == b) {
+                    continue;
                 }
 
-                for i in 0..candidates.len() {
-                    for j in i + 1..candidates.len() {
-                        for k in j + 1..candidates.len() {
-                            let (row1, col1) = candidates[i];
-                            let (row2, col2) = candidates[j];
-                            let (row3, col3) = candidates[k];
-
-                            if self.candidates[row1][col1] == self.candidates[row2][col2]
-                                && self.candidates[row2][col2] == self.candidates[row3][col3]
-                            {
-                                let triplet_values: HashSet<u8> =
-                                    self.candidates[row1][col1].clone();
-
-                                // Avoid simultaneous mutable & immutable borrow using split_at_mut()
-                                let (before, after) = self.candidates.split_at_mut(row1);
-                                let (target_row, after) = after.split_at_mut(1);
-                                let target = &mut target_row[0];
-
-                                let mut updated = false;
-                                for &(r, c) in unit {
-                                    if (r, c) != (row1, col1)
-                                        && (r, c) != (row2, col2)
-                                        && (r, c) != (row3, col3)
-                                    {
-                                        for &num in &triplet_values {
-                                            if target[c].remove(&num) {
-                                                updated = true;
-                                            }
-                                        }
-                                    }
-                                }
-                                if updated {
-                                    changed = true;
-                                }
-                            }
-                        }
-                    }
+                let eliminations: Vec<(usize, usize, u8)> = self
+                    .cells_with_candidate(self.units.cells(House::Box(b)), num)
+                    .into_iter()
+                    .filter(|cell| !cells.contains(cell))
+                    .map(|(r, c)| (r, c, num))
+                    .collect();
+                if !eliminations.is_empty() {
+                    return Some(SolveStep {
+                        eliminations,
+                        pattern: cells,
+                        digits: vec![num],
+                        houses: vec![line, House::Box(b)],
+                        ..SolveStep::new("box_line_reduction")
+                    });
                 }
             }
         }
+        None
+    }
+
+    /// Naked Pairs: Two cells in a house whose candidates are the same two digits.
+    /// Removes those digits from every other cell in the house.
+    pub fn naked_pairs(&mut self) -> bool {
+        self.naked_subset(2)
+    }
 
-        if changed {
-            self.increment_method_count("naked_triples");
+    /// Naked Triples: Three cells in a house whose candidates together are only three digits.
+    pub fn naked_triples(&mut self) -> bool {
+        self.naked_subset(3)
+    }
+
+    pub fn naked_subset(&mut self, size: usize) -> bool {
+        self.apply_all(|board| board.find_naked_subset(size))
+    }
+
+    /// General function for Naked Pairs/Triples: `size` cells in a house whose combined candidates
+    /// are exactly `size` digits must hold those digits, so the digits are removed from the rest of the house.
+    pub fn find_naked_subset(&self, size: usize) -> Option<SolveStep> {
+        for (index, house) in self.units.houses().enumerate() {
+            let cells: Vec<(usize, usize)> = house
+                .iter()
+                .copied()
+                .filter(|&(r, c)| {
+                    (2..=size).contains(&(self.candidates[r][c].count_ones() as usize))
+                })
+                .collect();
+
+            for subset in combinations(&cells, size) {
+                let union = subset
+                    .iter()
+                    .fold(0, |mask, &(r, c)| mask | self.candidates[r][c]);
+                if union.count_ones() as usize != size {
+                    continue;
+                }
+
+                let eliminations: Vec<(usize, usize, u8)> = house
+                    .iter()
+                    .filter(|cell| !subset.contains(cell))
+                    .flat_map(|&(r, c)| {
+                        digits(self.candidates[r][c] & union).map(move |num| (r, c, num))
+                    })
+                    .collect();
+                if !eliminations.is_empty() {
+                    return Some(SolveStep {
+                        eliminations,
+                        pattern: subset,
+                        digits: digits(union).collect(),
+                        houses: vec![self.units.label(index)],
+                        ..SolveStep::new(subset_name("naked", size))
+                    });
+                }
+            }
         }
-        changed
+        None
     }
 
-    /// Hidden Pairs: Two numbers appear in exactly two places within a unit. Remove other candidates from those cells.
+    /// Hidden Pairs: Two digits appear in exactly the same two cells of a house.
+    /// Removes all other candidates from those cells.
     pub fn hidden_pairs(&mut self) -> bool {
-        let changed = self.hidden_subgroup(2);
-        if changed {
-            self.increment_method_count("hidden_pairs");
-        }
-        changed
+        self.hidden_subgroup(2)
     }
 
-    /// Hidden Triples: Three numbers appear in exactly three places within a unit.
+    /// Hidden Triples: Three digits are confined to the same three cells of a house.
     pub fn hidden_triples(&mut self) -> bool {
-        let changed = self.hidden_subgroup(3);
-        if changed {
-            self.increment_method_count("hidden_triples");
-        }
-        changed
+        self.hidden_subgroup(3)
     }
 
-    /// General function for Hidden Pairs/Triples
     pub fn hidden_subgroup(&mut self, size: usize) -> bool {
-        let mut changed = false;
-        // Clone the units to avoid borrowing self while iterating
-        let units = self.units.clone();
-
-        for units in [units.row_units, units.col_units, units.box_units] {
-            for unit in units.iter() {
-                let mut candidate_map: std::collections::HashMap<u8, Vec<(usize, usize)>> =
-                    std::collections::HashMap::new();
-                for &(row, col) in unit {
-                    if self.grid[row][col] == 0 {
-                        for &num in &self.candidates[row][col] {
-                            candidate_map.entry(num).or_default().push((row, col));
-                        }
-                    }
+        self.apply_all(|board| board.find_hidden_subgroup(size))
+    }
+
+    /// General function for Hidden Pairs/Triples: `size` digits whose positions in a house are
+    /// confined to `size` cells must fill those cells, so all other candidates are removed from them.
+    pub fn find_hidden_subgroup(&self, size: usize) -> Option<SolveStep> {
+        for (index, house) in self.units.houses().enumerate() {
+            let mut positions: Vec<(u8, Vec<(usize, usize)>)> = Vec::new();
+            for num in 1..=self.size() as u8 {
+                let cells = self.cells_with_candidate(house, num);
+                if (2..=size).contains(&cells.len()) {
+                    positions.push((num, cells));
                 }
-                let candidate_entries: Vec<_> = candidate_map.into_iter().collect();
-                for i in 0..candidate_entries.len() {
-                    for j in i + 1..candidate_entries.len() {
-                        if size == 2
-                            && candidate_entries[i].1.len() == 2
-                            && candidate_entries[j].1.len() == 2
-                        {
-                            let set1: std::collections::HashSet<(usize, usize)> =
-                                candidate_entries[i].1.iter().cloned().collect();
-                            let set2: std::collections::HashSet<(usize, usize)> =
-                                candidate_entries[j].1.iter().cloned().collect();
-                            if set1 == set2 {
-                                let mut updated = false;
-                                for (row, col) in &set1 {
-                                    updated |= self.remove_all_candidates_except(
-                                        *row,
-                                        *col,
-                                        &[candidate_entries[i].0, candidate_entries[j].0],
-                                    );
-                                }
-                                if updated {
-                                    changed = true;
-                                }
-                            }
-                        }
-                        for k in j + 1..candidate_entries.len() {
-                            if size == 3
-                                && candidate_entries[i].1.len() == 3
-                                && candidate_entries[j].1.len() == 3
-                            {
-                                let set1: std::collections::HashSet<(usize, usize)> =
-                                    candidate_entries[i].1.iter().cloned().collect();
-                                let set2: std::collections::HashSet<(usize, usize)> =
-                                    candidate_entries[j].1.iter().cloned().collect();
-                                let set3: std::collections::HashSet<(usize, usize)> =
-                                    candidate_entries[k].1.iter().cloned().collect();
-                                if set1 == set2 && set2 == set3 {
-                                    let mut updated = false;
-                                    for (row, col) in &set1 {
-                                        updated |= self.remove_all_candidates_except(
-                                            *row,
-                                            *col,
-                                            &[candidate_entries[i].0, candidate_entries[j].0],
-                                        );
-                                    }
-                                    if updated {
-                                        changed = true;
-                                    }
-                                }
-                            }
-                        }
-                    }
+            }
+
+            for subset in combinations(&(0..positions.len()).collect::<Vec<_>>(), size) {
+                let digit_mask = subset.iter().fold(0, |mask, &i| mask | bit(positions[i].0));
+                let mut cells: Vec<(usize, usize)> = subset
+                    .iter()
+                    .flat_map(|&i| positions[i].1.iter().copied())
+                    .collect();
+                cells.sort();
+                cells.dedup();
+                if cells.len() != size {
+                    continue;
+                }
+
+                let eliminations: Vec<(usize, usize, u8)> = cells
+                    .iter()
+                    .flat_map(|&(r, c)| {
+                        digits(self.candidates[r][c] & !digit_mask).map(move |num| (r, c, num))
+                    })
+                    .collect();
+                if !eliminations.is_empty() {
+                    return Some(SolveStep {
+                        eliminations,
+                        pattern: cells,
+                        digits: digits(digit_mask).collect(),
+                        houses: vec![self.units.label(index)],
+                        ..SolveStep::new(subset_name("hidden", size))
+                    });
                 }
             }
         }
-        changed
+        None
     }
 
+    /// X-Wing: Basic fish of size 2.
     pub fn x_wing(&mut self) -> bool {
-        let mut changed = false;
+        self.basic_fish(2)
+    }
 
-        for num in 1..=9 {
-            // Check rows
-            for row1 in 0..8 {
-                for row2 in row1 + 1..9 {
-                    let mut cols = Vec::new();
-                    for col in 0..9 {
-                        if self.grid[row1][col] == 0 && self.grid[row2][col] == 0 {
-                            let candidates1 = &self.candidates[row1][col];
-                            let candidates2 = &self.candidates[row2][col];
-
-                            if candidates1.contains(&num) && candidates2.contains(&num) {
-                                cols.push(col);
-                            }
-                        }
-                    }
+    /// Swordfish: Basic fish of size 3.
+    pub fn swordfish(&mut self) -> bool {
+        self.basic_fish(3)
+    }
 
-                    if cols.len() == 2 {
-                        let (col1, col2) = (cols[0], cols[1]);
-
-                        let mut valid = true;
-                        for r in 0..9 {
-                            if r != row1
-                                && r != row2
-                                && self.grid[r][col1] == 0
-                                && self.candidates[r][col1].contains(&num)
-                            {
-                                valid = false;
-                            }
-                            if r != row1
-                                && r != row2
-                                && self.grid[r][col2] == 0
-                                && self.candidates[r][col2].contains(&num)
-                            {
-                                valid = false;
-                            }
-                        }
+    /// Jellyfish: Basic fish of size 4.
+    pub fn jellyfish(&mut self) -> bool {
+        self.basic_fish(4)
+    }
 
-                        if valid {
-                            for r in 0..9 {
-                                if r != row1 && r != row2 {
-                                    if self.candidates[r][col1].remove(&num)
-                                        || self.candidates[r][col2].remove(&num)
-                                    {
-                                        changed = true;
-                                    }
-                                }
-                            }
-                        }
+    /// Finned/Sashimi X-Wing.
+    pub fn finned_x_wing(&mut self) -> bool {
+        self.finned_fish(2)
+    }
+
+    /// Finned/Sashimi Swordfish.
+    pub fn finned_swordfish(&mut self) -> bool {
+        self.finned_fish(3)
+    }
+
+    /// Finned/Sashimi Jellyfish.
+    pub fn finned_jellyfish(&mut self) -> bool {
+        self.finned_fish(4)
+    }
+
+    pub fn basic_fish(&mut self, size: usize) -> bool {
+        self.apply_all(|board| board.find_basic_fish(size))
+    }
+
+    /// Basic Fish: If a digit's candidates in `size` base lines (rows or columns) are confined to
+    /// `size` cover lines, the digit is removed from the cover lines outside the base lines.
+    pub fn find_basic_fish(&self, size: usize) -> Option<SolveStep> {
+        for num in 1..=self.size() as u8 {
+            for rows_as_base in [true, false] {
+                let positions = self.fish_positions(num, rows_as_base);
+                let base_lines: Mask = (0..self.size())
+                    .filter(|&line| (2..=size).contains(&(positions[line].count_ones() as usize)))
+                    .fold(0, |mask, line| mask | 1 << line);
+
+                for base in subsets(base_lines, size) {
+                    let cover = indices(base).fold(0, |mask, line| mask | positions[line]);
+                    if cover.count_ones() as usize != size {
+                        continue;
+                    }
+
+                    let eliminations = self.fish_eliminations(
+                        num,
+                        rows_as_base,
+                        self.units.shape().all_digits() & !base,
+                        cover,
+                    );
+                    if !eliminations.is_empty() {
+                        return Some(SolveStep {
+                            eliminations,
+                            pattern: fish_pattern(&positions, rows_as_base, base),
+                            digits: vec![num],
+                            houses: fish_houses(rows_as_base, base),
+                            ..SolveStep::new(fish_name(size))
+                        });
                     }
                 }
             }
+        }
+        None
+    }
 
-            // Check columns
-            for col1 in 0..8 {
-                for col2 in col1 + 1..9 {
-                    let mut rows = Vec::new();
-                    for row in 0..9 {
-                        if self.grid[row][col1] == 0 && self.grid[row][col2] == 0 {
-                            let candidates1 = &self.candidates[row][col1];
-                            let candidates2 = &self.candidates[row][col2];
-
-                            if candidates1.contains(&num) && candidates2.contains(&num) {
-                                rows.push(row);
-                            }
-                        }
+    pub fn finned_fish(&mut self, size: usize) -> bool {
+        self.apply_all(|board| board.find_finned_fish(size))
+    }
+
+    /// Finned Fish: A basic fish whose base lines also hold extra candidates (fins) that all lie in
+    /// one box. Either a fin is true or the fish holds, so the digit is removed from cover-line
+    /// cells outside the base lines that share the fin box. The fish is sashimi when a base line
+    /// would be left with fewer than two candidates without its fins.
+    pub fn find_finned_fish(&self, size: usize) -> Option<SolveStep> {
+        for num in 1..=self.size() as u8 {
+            for rows_as_base in [true, false] {
+                let positions = self.fish_positions(num, rows_as_base);
+                let base_lines: Mask = (0..self.size())
+                    .filter(|&line| positions[line] != 0)
+                    .fold(0, |mask, line| mask | 1 << line);
+                // The fins share a box, so they can't reach more lines than a box crosses
+                let fin_span = self.box_span(rows_as_base);
+
+                for base in subsets(base_lines, size) {
+                    let union = indices(base).fold(0, |mask, line| mask | positions[line]);
+                    let reached = union.count_ones() as usize;
+                    if reached <= size || reached > size + fin_span {
+                        continue;
                     }
 
-                    if rows.len() == 2 {
-                        let (row1, row2) = (rows[0], rows[1]);
-
-                        let mut valid = true;
-                        for c in 0..9 {
-                            if c != col1
-                                && c != col2
-                                && self.grid[row1][c] == 0
-                                && self.candidates[row1][c].contains(&num)
-                            {
-                                valid = false;
-                            }
-                            if c != col1
-                                && c != col2
-                                && self.grid[row2][c] == 0
-                                && self.candidates[row2][c].contains(&num)
-                            {
-                                valid = false;
-                            }
+                    for cover in subsets(union, size) {
+                        // Every base line must still touch the cover lines
+                        if indices(base).any(|line| positions[line] & cover == 0) {
+                            continue;
+                        }
+
+                        // All fins must share a single box, which need not be 3x3 in Jigsaw Sudoku
+                        let fins: Vec<(usize, usize)> = indices(base)
+                            .flat_map(|line| {
+                                indices(positions[line] & !cover)
+                                    .map(move |i| fish_cell(rows_as_base, line, i))
+                            })
+                            .collect();
+                        let fin_box = self.units.box_of(fins[0].0, fins[0].1);
+                        if fins
+                            .iter()
+                            .any(|&(r, c)| self.units.box_of(r, c) != fin_box)
+                        {
+                            continue;
                         }
 
-                        if valid {
-                            for c in 0..9 {
-                                if c != col1 && c != col2 {
-                                    if self.candidates[row1][c].remove(&num)
-                                        || self.candidates[row2][c].remove(&num)
-                                    {
-                                        changed = true;
-                                    }
-                                }
-                            }
+                        let eliminations: Vec<(usize, usize, u8)> = self
+                            .fish_eliminations(
+                                num,
+                                rows_as_base,
+                                self.units.shape().all_digits() & !base,
+                                cover,
+                            )
+                            .into_iter()
+                            .filter(|&(r, c, _)| self.units.box_of(r, c) == fin_box)
+                            .collect();
+                        if !eliminations.is_empty() {
+                            let sashimi = indices(base)
+                                .any(|line| (positions[line] & cover).count_ones() < 2);
+                            return Some(SolveStep {
+                                eliminations,
+                                pattern: fish_pattern(&positions, rows_as_base, base),
+                                digits: vec![num],
+                                houses: fish_houses(rows_as_base, base),
+                                ..SolveStep::new(finned_fish_name(size, sashimi))
+                            });
                         }
                     }
                 }
             }
         }
+        None
+    }
 
-        if changed {
-            self.increment_method_count("x_wing");
+    /// The most columns (or rows, when `across_cols` is false) any one box reaches into.
+    fn box_span(&self, across_cols: bool) -> usize {
+        (0..self.size())
+            .map(|b| {
+                let lines = self
+                    .units
+                    .cells(House::Box(b))
+                    .iter()
+                    .fold(0, |mask: Mask, &(r, c)| {
+                        mask | 1 << if across_cols { c } else { r }
+                    });
+                lines.count_ones() as usize
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// For each base line, a bitmask of the cover lines where `num` is still a candidate.
+    fn fish_positions(&self, num: u8, rows_as_base: bool) -> Vec<Mask> {
+        let mut positions = vec![0; self.size()];
+        for (line, mask) in positions.iter_mut().enumerate() {
+            for i in 0..self.size() {
+                let (r, c) = fish_cell(rows_as_base, line, i);
+                if self.candidates[r][c] & bit(num) != 0 {
+                    *mask |= 1 << i;
+                }
+            }
         }
+        positions
+    }
 
-        changed
+    /// Candidates of `num` where the given `lines` cross the given `covers`.
+    fn fish_eliminations(
+        &self,
+        num: u8,
+        rows_as_base: bool,
+        lines: Mask,
+        covers: Mask,
+    ) -> Vec<(usize, usize, u8)> {
+        let mut eliminations = Vec::new();
+        for cover_line in indices(covers) {
+            for line in indices(lines) {
+                let (r, c) = fish_cell(rows_as_base, line, cover_line);
+                if self.candidates[r][c] & bit(num) != 0 {
+                    eliminations.push((r, c, num));
+                }
+            }
+        }
+        eliminations
     }
 
     pub fn y_wing(&mut self) -> bool {
-        let mut changed = false;
+        self.apply_all(Self::find_y_wing)
+    }
 
-        let mut bivalue_cells = Vec::new();
+    /// Y-Wing (XY-Wing): A pivot {x,y} sees two pincers {x,z} and {y,z}. Whichever value the pivot
+    /// takes, one pincer is z, so z is removed from every cell that sees both pincers.
+    pub fn find_y_wing(&self) -> Option<SolveStep> {
+        let bivalue_cells = self.cells_with_candidate_count(2, 2);
+
+        for &(pr, pc) in &bivalue_cells {
+            let pivot = self.candidates[pr][pc];
+            let pincers: Vec<(usize, usize)> = bivalue_cells
+                .iter()
+                .copied()
+                .filter(|&(r, c)| {
+                    self.units.sees((pr, pc), (r, c))
+                        && (self.candidates[r][c] & pivot).count_ones() == 1
+                })
+                .collect();
+
+            for (i, &(r1, c1)) in pincers.iter().enumerate() {
+                for &(r2, c2) in &pincers[i + 1..] {
+                    let wing1 = self.candidates[r1][c1];
+                    let wing2 = self.candidates[r2][c2];
+
+                    // The pincers must share the pivot's two different digits
+                    if wing1 & pivot == wing2 & pivot {
+                        continue;
+                    }
 
-        for row in 0..9 {
-            for col in 0..9 {
-                if self.grid[row][col] == 0 && self.candidates[row][col].len() == 2 {
-                    let values: Vec<u8> = self.candidates[row][col].iter().cloned().collect();
-                    bivalue_cells.push((row, col, values[0], values[1]));
+                    let z = wing1 & !pivot;
+                    if z != wing2 & !pivot {
+                        continue;
+                    }
+
+                    let eliminations = self.common_peer_eliminations(&[(r1, c1), (r2, c2)], z);
+                    if !eliminations.is_empty() {
+                        return Some(SolveStep {
+                            eliminations,
+                            pattern: vec![(pr, pc), (r1, c1), (r2, c2)],
+                            digits: digits(pivot | z).collect(),
+                            ..SolveStep::new("y_wing")
+                        });
+                    }
                 }
             }
         }
+        None
+    }
 
-        for i in 0..bivalue_cells.len() {
-            let (row1, col1, a, b) = bivalue_cells[i];
-
-            for j in i + 1..bivalue_cells.len() {
-                let (row2, col2, x, y) = bivalue_cells[j];
-
-                if row1 == row2 || col1 == col2 || (row1 / 3 == row2 / 3 && col1 / 3 == col2 / 3) {
-                    if (a == x || a == y) && (b != x && b != y) {
-                        let pivot = a;
-                        let wing1 = b;
-                        let wing2 = if a == x { y } else { x };
-
-                        for k in j + 1..bivalue_cells.len() {
-                            let (row3, col3, c, d) = bivalue_cells[k];
-
-                            if (row2 == row3
-                                || col2 == col3
-                                || (row2 / 3 == row3 / 3 && col2 / 3 == col3 / 3))
-                                && (c == wing2 && d == pivot || d == wing2 && c == pivot)
-                            {
-                                let elimination_target = if c == pivot { d } else { c };
-
-                                for r in 0..9 {
-                                    for c in 0..9 {
-                                        if (r, c) != (row1, col1)
-                                            && (r, c) != (row2, col2)
-                                            && (r, c) != (row3, col3)
-                                        {
-                                            if self.candidates[r][c].remove(&elimination_target) {
-                                                changed = true;
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        }
+    pub fn xyz_wing(&mut self) -> bool {
+        self.apply_all(Self::find_xyz_wing)
+    }
+
+    /// XYZ-Wing: A pivot {x,y,z} sees two pincers {x,z} and {y,z}. One of the three cells must be z,
+    /// so z is removed from every cell that sees the pivot and both pincers.
+    pub fn find_xyz_wing(&self) -> Option<SolveStep> {
+        let bivalue_cells = self.cells_with_candidate_count(2, 2);
+
+        for (pr, pc) in self.cells_with_candidate_count(3, 3) {
+            let pivot = self.candidates[pr][pc];
+            let pincers: Vec<(usize, usize)> = bivalue_cells
+                .iter()
+                .copied()
+                .filter(|&(r, c)| {
+                    self.units.sees((pr, pc), (r, c)) && self.candidates[r][c] & !pivot == 0
+                })
+                .collect();
+
+            for (i, &(r1, c1)) in pincers.iter().enumerate() {
+                for &(r2, c2) in &pincers[i + 1..] {
+                    let wing1 = self.candidates[r1][c1];
+                    let wing2 = self.candidates[r2][c2];
+                    if wing1 == wing2 {
+                        continue;
+                    }
+
+                    let wing = [(pr, pc), (r1, c1), (r2, c2)];
+                    let eliminations = self.common_peer_eliminations(&wing, wing1 & wing2);
+                    if !eliminations.is_empty() {
+                        return Some(SolveStep {
+                            eliminations,
+                            pattern: wing.to_vec(),
+                            digits: digits(pivot).collect(),
+                            ..SolveStep::new("xyz_wing")
+                        });
                     }
                 }
             }
         }
+        None
+    }
+
+    pub fn wxyz_wing(&mut self) -> bool {
+        self.apply_all(Self::find_wxyz_wing)
+    }
+
+    /// WXYZ-Wing: Four cells holding exactly four digits between them, where every digit except z
+    /// is restricted (all cells holding it see each other). At most three cells can take a
+    /// restricted digit, so one of the cells holding z must be z, and z is removed from every cell
+    /// that sees all of them.
+    pub fn find_wxyz_wing(&self) -> Option<SolveStep> {
+        let cells = self.cells_with_candidate_count(2, 4);
+
+        for &pivot in &cells {
+            let wings: Vec<(usize, usize)> = cells
+                .iter()
+                .copied()
+                .filter(|&cell| self.units.sees(pivot, cell))
+                .collect();
+
+            for combo in combinations(&wings, 3) {
+                let wing = [pivot, combo[0], combo[1], combo[2]];
+                let union = wing
+                    .iter()
+                    .fold(0, |mask, &(r, c)| mask | self.candidates[r][c]);
+                if union.count_ones() != 4 {
+                    continue;
+                }
 
-        if changed {
-            self.increment_method_count("y_wing");
+                let mut non_restricted = Vec::new();
+                for num in digits(union) {
+                    let holders: Vec<(usize, usize)> = wing
+                        .iter()
+                        .copied()
+                        .filter(|&(r, c)| self.candidates[r][c] & bit(num) != 0)
+                        .collect();
+                    let restricted = holders
+                        .iter()
+                        .enumerate()
+                        .all(|(i, &a)| holders[i + 1..].iter().all(|&b| self.units.sees(a, b)));
+                    if !restricted {
+                        non_restricted.push((num, holders));
+                    }
+                }
+
+                if let [(z, holders)] = non_restricted.as_slice() {
+                    let eliminations = self.common_peer_eliminations(holders, bit(*z));
+                    if !eliminations.is_empty() {
+                        return Some(SolveStep {
+                            eliminations,
+                            pattern: wing.to_vec(),
+                            digits: digits(union).collect(),
+                            ..SolveStep::new("wxyz_wing")
+                        });
+                    }
+                }
+            }
         }
+        None
+    }
 
-        changed
+    /// Empty cells whose candidate count lies within `min..=max`.
+    fn cells_with_candidate_count(&self, min: usize, max: usize) -> Vec<(usize, usize)> {
+        let mut cells = Vec::new();
+        for row in 0..self.size() {
+            for col in 0..self.size() {
+                let count = self.candidates[row][col].count_ones() as usize;
+                if (min..=max).contains(&count) {
+                    cells.push((row, col));
+                }
+            }
+        }
+        cells
+    }
+
+    /// The cells in `cells` that still have `num` as a candidate.
+    fn cells_with_candidate(&self, cells: &[(usize, usize)], num: u8) -> Vec<(usize, usize)> {
+        cells
+            .iter()
+            .copied()
+            .filter(|&(r, c)| self.candidates[r][c] & bit(num) != 0)
+            .collect()
     }
 
-    fn is_solved(&self) -> bool {
+    /// Candidates among `targets` in every cell (outside `cells`) that sees all of `cells`.
+    fn common_peer_eliminations(
+        &self,
+        cells: &[(usize, usize)],
+        targets: Mask,
+    ) -> Vec<(usize, usize, u8)> {
+        let mut eliminations = Vec::new();
+        for &(row, col) in self.units.peers(cells[0].0, cells[0].1) {
+            if !cells.contains(&(row, col))
+                && cells.iter().all(|&cell| self.units.sees((row, col), cell))
+            {
+                for num in digits(self.candidates[row][col] & targets) {
+                    eliminations.push((row, col, num));
+                }
+            }
+        }
+        eliminations
+    }
+
+    pub fn is_solved(&self) -> bool {
         self.grid
             .iter()
             .all(|row| row.iter().all(|&cell| cell != 0))
     }
 
     fn is_valid(&self, row: usize, col: usize, num: u8) -> bool {
-        if self.grid[row].contains(&num) {
-            return false;
-        }
+        self.placed_digits(row, col) & bit(num) == 0
+            && self.fits_cage(row, col, num)
+            && self.fits_sandwich(row, col, num)
+    }
+
+    /// Removes `num` from the candidates of (row, col), returning whether it was there.
+    fn remove_candidate(&mut self, row: usize, col: usize, num: u8) -> bool {
+        self.remove_candidates(row, col, bit(num))
+    }
+
+    /// Removes the `digits` from the candidates of (row, col), returning whether any were there.
+    fn remove_candidates(&mut self, row: usize, col: usize, digits: Mask) -> bool {
+        let candidates = &mut self.candidates[row][col];
+        let removed = *candidates & digits != 0;
+        *candidates &= !digits;
+        removed
+    }
+
+    /// Places `value` and removes it from the candidates of every peer. A value clashing with
+    /// a peer is still placed, so the solver's contradiction check reports the duplicate.
+    fn set_value(&mut self, row: usize, col: usize, value: u8) {
+        self.place(row, col, value);
+        self.candidates[row][col] = 0; // No candidates left
 
-        if self.grid.iter().any(|r| r[col] == num) {
-            return false;
+        // Every cell sharing a house
+        let units = Arc::clone(&self.units);
+        for &(r, c) in units.peers(row, col) {
+            self.remove_candidate(r, c, value);
         }
+    }
+}
 
-        let box_row = (row / 3) * 3;
-        let box_col = (col / 3) * 3;
-        for r in 0..3 {
-            for c in 0..3 {
-                if self.grid[box_row + r][box_col + c] == num {
-                    return false;
-                }
+/// Maps a (base line, cover line) pair to a cell, with base lines being rows or columns.
+fn fish_cell(rows_as_base: bool, line: usize, cover: usize) -> (usize, usize) {
+    if rows_as_base {
+        (line, cover)
+    } else {
+        (cover, line)
+    }
+}
+
+/// Cells of the base lines holding the fish digit.
+fn fish_pattern(positions: &[Mask], rows_as_base: bool, base: Mask) -> Vec<(usize, usize)> {
+    indices(base)
+        .flat_map(|line| indices(positions[line]).map(move |i| fish_cell(rows_as_base, line, i)))
+        .collect()
+}
+
+fn fish_houses(rows_as_base: bool, base: Mask) -> Vec<House> {
+    indices(base)
+        .map(|line| {
+            if rows_as_base {
+                House::Row(line)
+            } else {
+                House::Col(line)
             }
+        })
+        .collect()
+}
+
+fn fish_name(size: usize) -> &'static str {
+    match size {
+        2 => "x_wing",
+        3 => "swordfish",
+        4 => "jellyfish",
+        _ => "fish",
+    }
+}
+
+fn finned_fish_name(size: usize, sashimi: bool) -> &'static str {
+    match (size, sashimi) {
+        (2, false) => "finned_x_wing",
+        (3, false) => "finned_swordfish",
+        (4, false) => "finned_jellyfish",
+        (2, true) => "sashimi_x_wing",
+        (3, true) => "sashimi_swordfish",
+        (4, true) => "sashimi_jellyfish",
+        (_, false) => "finned_fish",
+        (_, true) => "sashimi_fish",
+    }
+}
+
+/// Technique name for a naked or hidden subset of the given size.
+fn subset_name(kind: &str, size: usize) -> &'static str {
+    match (kind, size) {
+        ("naked", 2) => "naked_pairs",
+        ("naked", 3) => "naked_triples",
+        ("naked", _) => "naked_quads",
+        (_, 2) => "hidden_pairs",
+        (_, 3) => "hidden_triples",
+        _ => "hidden_quads",
+    }
+}
+
+/// All `k`-element combinations of `items`, in order.
+fn combinations<T: Copy>(items: &[T], k: usize) -> Vec<Vec<T>> {
+    if k == 0 {
+        return vec![Vec::new()];
+    }
+    let mut result = Vec::new();
+    for i in 0..items.len() {
+        for mut rest in combinations(&items[i + 1..], k - 1) {
+            rest.insert(0, items[i]);
+            result.push(rest);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const SAMPLES: [&str; 6] = [
+        "sudoku.txt",
+        "killer.txt",
+        "sandwich.txt",
+        "xsudoku.txt",
+        "jigsaw.txt",
+        "sizes.txt",
+    ];
+
+    /// Puzzles logic alone can't finish, to reach the larger fish and wings.
+    const HARD: &str = "\
+1....7.9..3..2...8..96..5....53..9...1..8...26....4...3......1..4......7..7...3.. # AI Escargot
+1.......2.9.4...5...6...7...5.9.3.......7.......85..4.7.....6...3...9.8...2.....1 # Easter Monster
+8..........36......7..9.2...5...7.......457.....1...3...1....68..85...1..9....4.. # Inkala 2012
+..............3.85..1.2.......5.7.....4...1...9.......5......73..2.1........4...9 # Minimum 17
+.2.4.37.........32........4.4.2...7.8...5.........1...5.....9...3.9....7..1..86.. # Hard 06
+4.....8.5.3..........7......2.....6.....8.4......1.......6.3.7.5..2.....1.4...... # Hard 07
+52...6.........7.13...........4..8..6......5...........418.........3..2...87..... # Hard 08
+6.....8.3.4.7.................5.4.7.3..2.....1.6.......2.....5.....8.6......1.... # Hard 09
+48.3............71.2.......7.5....6....2..8.............1.76...3.....4......5.... # Hard 10
+....14....3....2...7..........9...3.6.1.............8.2.....1.4....5.6.....7.8... # Hard 11
+......52..8.4......3...9...5.1...6..2..7........3.....6...1..........7.4.......3. # Hard 12
+6.2.5.........3.4..........43...8....1....2........7..5..27...........81...6..... # Hard 13
+";
+
+    fn puzzles() -> Vec<Puzzle> {
+        let mut puzzles: Vec<Puzzle> = SAMPLES
+            .iter()
+            .flat_map(|file| {
+                read_sudoku_puzzles(&format!("{}/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap()
+            })
+            .collect();
+        let context = ParseContext {
+            path: "hard".to_string(),
+            strict: true,
+        };
+        for puzzle in PuzzleReader::new(Cursor::new(HARD), context) {
+            puzzles.push(puzzle.unwrap());
         }
-        true
+        puzzles
     }
 
-    fn find_empty(&self) -> Option<(usize, usize)> {
-        for row in 0..9 {
-            for col in 0..9 {
-                if self.grid[row][col] == 0 {
-                    return Some((row, col));
+    /// Every step any technique finds, from the givens until logic stalls, keeps each cell's
+    /// digit in the unique solution.
+    #[test]
+    fn techniques_are_consistent_with_the_solution() {
+        let solver = Solver::new();
+        let mut checked = 0;
+        for puzzle in puzzles() {
+            let mut board = puzzle.board().clone();
+            let [solution] = &board.count_solutions(2)[..] else {
+                continue;
+            };
+            loop {
+                for technique in TECHNIQUES {
+                    let Some(step) = (technique.find)(&board) else {
+                        continue;
+                    };
+                    for &(row, col, num) in &step.placements {
+                        assert_eq!(
+                            solution[row][col],
+                            num,
+                            "{} places {} at r{}c{} in {}",
+                            step.technique,
+                            num,
+                            row + 1,
+                            col + 1,
+                            puzzle.name()
+                        );
+                    }
+                    for &(row, col, num) in &step.eliminations {
+                        assert_ne!(
+                            solution[row][col],
+                            num,
+                            "{} removes {} from r{}c{} in {}",
+                            step.technique,
+                            num,
+                            row + 1,
+                            col + 1,
+                            puzzle.name()
+                        );
+                    }
+                    checked += 1;
+                }
+                if solver.step(&mut board).is_none() {
+                    break;
                 }
             }
         }
-        None
+        assert!(checked > 1000, "only {} steps checked", checked);
     }
 
-    fn remove_all_candidates_except(&mut self, row: usize, col: usize, allowed: &[u8]) -> bool {
-        if self.grid[row][col] != 0 {
-            return false;
+    /// Both backends find the same solutions, none of them for a broken grid, and neither
+    /// goes past its limit.
+    #[test]
+    fn dlx_and_backtracking_agree() {
+        for puzzle in puzzles() {
+            let board = puzzle.board();
+            if board.has_sum_rules() {
+                continue;
+            }
+            let mut dlx = board.dlx_solutions(2);
+            let mut search = board.search_solutions(2);
+            dlx.sort();
+            search.sort();
+            assert_eq!(dlx.len(), search.len(), "{}", puzzle.name());
+            if dlx.len() == 1 {
+                assert_eq!(dlx, search, "{}", puzzle.name());
+            }
+            assert!(board.dlx_solutions(0).is_empty());
+            assert!(board.search_solutions(0).is_empty());
         }
 
-        let allowed_set: HashSet<u8> = allowed.iter().cloned().collect();
-        let candidates = &mut self.candidates[row][col]; // Mutable borrow of precomputed candidates
+        let mut grid = vec![vec![0; 9]; 9];
+        grid[0][0] = 5;
+        grid[0][8] = 5;
+        let broken = SudokuBoard::from(grid);
+        assert!(broken.dlx_solutions(2).is_empty());
+        assert!(broken.search_solutions(2).is_empty());
+    }
 
-        // Check if we are actually removing something
-        let before_size = candidates.len();
-        candidates.retain(|&x| allowed_set.contains(&x));
-        let after_size = candidates.len();
+    #[test]
+    fn multiple_solutions_are_counted_up_to_the_limit() {
+        let empty = SudokuBoard::new();
+        assert_eq!(empty.count_solutions(3).len(), 3);
+        assert!(!empty.has_unique_solution());
+    }
 
-        if after_size < before_size {
-            if after_size == 1 {
-                self.grid[row][col] = *candidates.iter().next().unwrap();
+    /// Following hints alone solves every unique puzzle: each is the first step any technique
+    /// finds, or a cell of the solution once they all stall, and asking never changes the board.
+    /// Killer and sandwich boards are left out, as each hint searches them for solutions.
+    #[test]
+    fn hints_solve_unique_puzzles() {
+        let mut revealed = 0;
+        for puzzle in puzzles() {
+            let mut board = puzzle.board().clone();
+            if board.has_sum_rules() {
+                continue;
             }
-            return true; // We actually removed something
+            let [solution] = &board.count_solutions(2)[..] else {
+                assert_eq!(board.next_hint(), None, "{}", puzzle.name());
+                continue;
+            };
+            loop {
+                let before = board.clone();
+                let Some(hint) = board.next_hint() else {
+                    break;
+                };
+                assert_eq!(board.grid, before.grid);
+                assert_eq!(board.candidates, before.candidates);
+
+                let simplest = TECHNIQUES
+                    .iter()
+                    .find_map(|technique| (technique.find)(&board));
+                match simplest {
+                    Some(step) => assert_eq!(hint, step, "{}", puzzle.name()),
+                    None => {
+                        let [(row, col, num)] = hint.placements[..] else {
+                            panic!("{} reveals {:?}", puzzle.name(), hint.placements);
+                        };
+                        assert_eq!((hint.technique, num), ("solution", solution[row][col]));
+                        revealed += 1;
+                    }
+                }
+                board.apply_step(hint);
+            }
+            assert_eq!(&board.grid, solution, "{}", puzzle.name());
         }
-
-        false
+        assert!(revealed > 0, "no puzzle needed a cell revealed");
+        assert_eq!(SudokuBoard::new().next_hint(), None);
     }
 
-    fn set_value(&mut self, row: usize, col: usize, value: u8) {
-        if !self.is_valid(row, col, value) {
-            println!("set_value is not valid");
-            return;
+    #[test]
+    fn strategies_are_disabled_by_name_or_alias() {
+        let mut solver = Solver::new();
+        for name in ["x_wing", "innies", "outies", "sashimi_x_wing"] {
+            assert!(solver.disable(name).is_ok(), "{}", name);
         }
-        self.grid[row][col] = value;
-        self.candidates[row][col].clear(); // No candidates left
+        assert!(matches!(
+            solver.disable("foo"),
+            Err(Error::UnknownStrategy(name)) if name == "foo"
+        ));
+    }
 
-        // Same row
-        for c in 0..9 {
-            if c != col {
-                self.candidates[row][c].remove(&value);
-            }
+    /// Puzzles with clashing givens or no solution get no rating rather than Extreme.
+    #[test]
+    fn only_solvable_puzzles_are_rated() {
+        for puzzle in puzzles()
+            .iter()
+            .filter(|puzzle| puzzle.has_unique_solution())
+        {
+            assert!(puzzle.rate().is_some(), "{}", puzzle.name());
         }
 
-        // Same column
-        for r in 0..9 {
-            if r != row {
-                self.candidates[r][col].remove(&value);
-            }
-        }
+        let mut grid = vec![vec![0; 9]; 9];
+        grid[0][0] = 5;
+        grid[0][1] = 5;
+        assert_eq!(SudokuBoard::from(grid).rate(), None);
+
+        // No clash among the givens, but r1c9 can't hold anything
+        let mut grid = vec![vec![0; 9]; 9];
+        grid[0][..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        grid[1][8] = 9;
+        assert_eq!(SudokuBoard::from(grid).rate(), None);
+    }
 
-        // Same box
-        let box_row = (row / 3) * 3;
-        let box_col = (col / 3) * 3;
-        for r in 0..3 {
-            for c in 0..3 {
-                let br = box_row + r;
-                let bc = box_col + c;
-                if (br != row || bc != col) {
-                    self.candidates[br][bc].remove(&value);
+    /// Dancing links counts a guess for each empty cell it fills, never for the givens, so a
+    /// grid with one cell left takes one guess and no backtracks.
+    #[test]
+    fn dlx_guesses_only_empty_cells() {
+        let mut grid = SudokuBoard::new().count_solutions(1).remove(0);
+        grid[4][4] = 0;
+        let mut board = SudokuBoard::from(grid);
+        assert!(board.solve_dlx());
+        assert_eq!((board.guesses, board.backtracks), (1, 0));
+    }
+
+    /// Every solve finishes with a grid that breaks no rule, whichever backend it ends with.
+    #[test]
+    fn solutions_are_valid() {
+        for backend in [Backend::Backtracking, Backend::Dlx] {
+            for mut puzzle in puzzles() {
+                let unique = puzzle.has_unique_solution();
+                let report = puzzle.solve_with(backend);
+                assert!(report.is_solved(), "{} with {:?}", puzzle.name(), backend);
+                assert!(
+                    puzzle.board().find_contradiction().is_none(),
+                    "{}",
+                    puzzle.name()
+                );
+                if unique {
+                    assert_eq!(vec![report.grid.clone()], puzzle.count_solutions(2));
                 }
             }
         }
@@ -0,0 +1,315 @@
+//! Pluggable solving strategies and the configurable pipeline that runs them
+
+use crate::prelude::*;
+
+use std::time::Instant;
+
+/// A logical solving technique that can be plugged into a `Solver`.
+pub trait Strategy: Send + Sync {
+    /// Unique name, used to enable and disable the strategy.
+    fn name(&self) -> &'static str;
+
+    /// Sudoku Explainer style weight; harder techniques weigh more.
+    fn difficulty(&self) -> f32;
+
+    /// Finds one step, applies it to the board (usually with `SudokuBoard::apply_step`)
+    /// and returns it, or returns `None` and leaves the board untouched.
+    fn apply(&self, board: &mut SudokuBoard) -> Option<SolveStep>;
+}
+
+/// A built-in technique: one of the board's `find_*` functions with a name and weight.
+#[derive(Clone, Copy)]
+pub struct Technique {
+    pub name: &'static str,
+    pub difficulty: f32,
+    pub find: fn(&SudokuBoard) -> Option<SolveStep>,
+}
+
+impl Strategy for Technique {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn difficulty(&self) -> f32 {
+        self.difficulty
+    }
+
+    fn apply(&self, board: &mut SudokuBoard) -> Option<SolveStep> {
+        let step = (self.find)(board)?;
+        board.apply_step(step.clone());
+        Some(step)
+    }
+}
+
+/// Every built-in technique, simplest first by Sudoku Explainer rating. Finned fish and the
+/// WXYZ-Wing, which Explainer doesn't rate, slot in just above their plain counterparts.
+/// The killer cage and sandwich techniques, which only apply to those variations, rank with
+/// the singles.
+pub const TECHNIQUES: &[Technique] = &[
+    Technique {
+        name: "hidden_singles",
+        difficulty: 1.5,
+        find: SudokuBoard::find_hidden_single,
+    },
+    Technique {
+        name: "cage_combinations",
+        difficulty: 2.0,
+        find: SudokuBoard::find_cage_combination,
+    },
+    Technique {
+        name: "sandwich_sums",
+        difficulty: 2.0,
+        find: SudokuBoard::find_sandwich,
+    },
+    Technique {
+        name: "naked_singles",
+        difficulty: 2.3,
+        find: SudokuBoard::find_naked_single,
+    },
+    Technique {
+        name: "innies_outies",
+        difficulty: 2.5,
+        find: SudokuBoard::find_innie_outie,
+    },
+    Technique {
+        name: "pointing_pairs",
+        difficulty: 2.6,
+        find: SudokuBoard::find_pointing_pair,
+    },
+    Technique {
+        name: "box_line_reduction",
+        difficulty: 2.8,
+        find: SudokuBoard::find_box_line_reduction,
+    },
+    Technique {
+        name: "naked_pairs",
+        difficulty: 3.0,
+        find: |board| board.find_naked_subset(2),
+    },
+    Technique {
+        name: "x_wing",
+        difficulty: 3.2,
+        find: |board| board.find_basic_fish(2),
+    },
+    Technique {
+        name: "hidden_pairs",
+        difficulty: 3.4,
+        find: |board| board.find_hidden_subgroup(2),
+    },
+    Technique {
+        name: "finned_x_wing",
+        difficulty: 3.4,
+        find: |board| board.find_finned_fish(2),
+    },
+    Technique {
+        name: "naked_triples",
+        difficulty: 3.6,
+        find: |board| board.find_naked_subset(3),
+    },
+    Technique {
+        name: "swordfish",
+        difficulty: 3.8,
+        find: |board| board.find_basic_fish(3),
+    },
+    Technique {
+        name: "hidden_triples",
+        difficulty: 4.0,
+        find: |board| board.find_hidden_subgroup(3),
+    },
+    Technique {
+        name: "finned_swordfish",
+        difficulty: 4.0,
+        find: |board| board.find_finned_fish(3),
+    },
+    Technique {
+        name: "y_wing",
+        difficulty: 4.2,
+        find: SudokuBoard::find_y_wing,
+    },
+    Technique {
+        name: "xyz_wing",
+        difficulty: 4.4,
+        find: SudokuBoard::find_xyz_wing,
+    },
+    Technique {
+        name: "wxyz_wing",
+        difficulty: 4.6,
+        find: SudokuBoard::find_wxyz_wing,
+    },
+    Technique {
+        name: "naked_quads",
+        difficulty: 5.0,
+        find: |board| board.find_naked_subset(4),
+    },
+    Technique {
+        name: "jellyfish",
+        difficulty: 5.2,
+        find: |board| board.find_basic_fish(4),
+    },
+    Technique {
+        name: "hidden_quads",
+        difficulty: 5.4,
+        find: |board| board.find_hidden_subgroup(4),
+    },
+    Technique {
+        name: "finned_jellyfish",
+        difficulty: 5.4,
+        find: |board| board.find_finned_fish(4),
+    },
+];
+
+/// Names some steps record in place of the built-in technique that found them, e.g. a
+/// sashimi X-Wing found by `finned_x_wing`, so they can be disabled by either name.
+pub const ALIASES: &[(&str, &str)] = &[
+    ("innies", "innies_outies"),
+    ("outies", "innies_outies"),
+    ("sashimi_x_wing", "finned_x_wing"),
+    ("sashimi_swordfish", "finned_swordfish"),
+    ("sashimi_jellyfish", "finned_jellyfish"),
+];
+
+/// Runs an ordered list of strategies, simplest first, and finishes with a search backend.
+///
+/// After every step the pipeline restarts from the first enabled strategy, so each step is
+/// made with the simplest technique that applies. Strategies can be switched off by name,
+/// either while building (`without`) or later (`disable`).
+pub struct Solver {
+    strategies: Vec<Box<dyn Strategy>>,
+    disabled: Vec<&'static str>,
+    /// `None` picks one to suit the board, see `Backend::for_size`.
+    backend: Option<Backend>,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    /// A solver with every built-in technique and the backend suited to each board.
+    pub fn new() -> Solver {
+        TECHNIQUES
+            .iter()
+            .fold(Solver::empty(), |solver, &technique| {
+                solver.strategy(technique)
+            })
+    }
+
+    /// A solver with no strategies, which goes straight to the backend.
+    pub fn empty() -> Solver {
+        Solver {
+            strategies: Vec::new(),
+            disabled: Vec::new(),
+            backend: None,
+        }
+    }
+
+    /// Appends a strategy to the end of the pipeline.
+    pub fn strategy(mut self, strategy: impl Strategy + 'static) -> Solver {
+        self.strategies.push(Box::new(strategy));
+        self
+    }
+
+    /// Sets the search used once no strategy applies.
+    pub fn backend(mut self, backend: Backend) -> Solver {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Builds the solver with the named strategy switched off.
+    pub fn without(mut self, name: &str) -> Result<Solver> {
+        self.disable(name)?;
+        Ok(self)
+    }
+
+    /// Switches the named strategy off. A name in `ALIASES` switches off its technique.
+    pub fn disable(&mut self, name: &str) -> Result<()> {
+        let name = self.lookup(name)?;
+        if !self.disabled.contains(&name) {
+            self.disabled.push(name);
+        }
+        Ok(())
+    }
+
+    /// Switches the named strategy back on.
+    pub fn enable(&mut self, name: &str) -> Result<()> {
+        let name = self.lookup(name)?;
+        self.disabled.retain(|&disabled| disabled != name);
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<&'static str> {
+        let technique = ALIASES
+            .iter()
+            .find(|&&(alias, _)| alias == name)
+            .map_or(name, |&(_, technique)| technique);
+        self.strategies
+            .iter()
+            .map(|strategy| strategy.name())
+            .find(|&known| known == technique)
+            .ok_or_else(|| Error::UnknownStrategy(name.to_string()))
+    }
+
+    /// The enabled strategies, in the order they are tried.
+    pub fn strategies(&self) -> impl Iterator<Item = &dyn Strategy> {
+        self.strategies
+            .iter()
+            .map(|strategy| strategy.as_ref())
+            .filter(|strategy| !self.disabled.contains(&strategy.name()))
+    }
+
+    /// Applies one step with the first enabled strategy that finds one.
+    pub fn step(&self, board: &mut SudokuBoard) -> Option<SolveStep> {
+        self.strategies().find_map(|strategy| strategy.apply(board))
+    }
+
+    /// Solves logically as far as possible, then finishes with the backend. The position is
+    /// checked before solving and after every step, stopping at the first contradiction.
+    pub fn solve(&self, board: &mut SudokuBoard) -> SolveReport {
+        let start_time = Instant::now();
+        let (outcome, method) = self.run(board);
+        board.report(outcome, method, start_time.elapsed())
+    }
+
+    fn run(&self, board: &mut SudokuBoard) -> (SolveOutcome, SolveMethod) {
+        if let Some(contradiction) = board.find_contradiction() {
+            return (
+                SolveOutcome::Contradiction(contradiction),
+                SolveMethod::Logical,
+            );
+        }
+        while !board.is_solved() {
+            if self.step(board).is_none() {
+                break;
+            }
+            if let Some(contradiction) = board.find_contradiction() {
+                return (
+                    SolveOutcome::Contradiction(contradiction),
+                    SolveMethod::Logical,
+                );
+            }
+        }
+        if board.is_solved() {
+            return (SolveOutcome::Solved, SolveMethod::Logical);
+        }
+
+        let backend = self
+            .backend
+            .unwrap_or_else(|| Backend::for_size(board.size()));
+        let success = match backend {
+            Backend::Backtracking => board.brute_force(),
+            Backend::Dlx => board.solve_dlx(),
+        };
+        let outcome = if success {
+            SolveOutcome::Solved
+        } else {
+            SolveOutcome::NoSolution
+        };
+        let method = match backend {
+            Backend::Dlx if board.has_sum_rules() => SolveMethod::Backtracking,
+            backend => backend.into(),
+        };
+        (outcome, method)
+    }
+}
@@ -0,0 +1,203 @@
+//! Structured record of each logical solving step
+
+use crate::prelude::*;
+
+use std::fmt;
+
+/// A single application of a technique: what it placed or eliminated, and the pattern that justifies it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SolveStep {
+    /// Technique name, as counted in `method_counts`.
+    pub technique: &'static str,
+    /// Digits placed, as (row, col, digit).
+    pub placements: Vec<(usize, usize, u8)>,
+    /// Candidates removed, as (row, col, digit).
+    pub eliminations: Vec<(usize, usize, u8)>,
+    /// Cells forming the pattern.
+    pub pattern: Vec<(usize, usize)>,
+    /// Digits the pattern is built on.
+    pub digits: Vec<u8>,
+    /// Houses the pattern lives in.
+    pub houses: Vec<House>,
+}
+
+impl SolveStep {
+    pub fn new(technique: &'static str) -> SolveStep {
+        SolveStep {
+            technique,
+            placements: Vec::new(),
+            eliminations: Vec::new(),
+            pattern: Vec::new(),
+            digits: Vec::new(),
+            houses: Vec::new(),
+        }
+    }
+
+    /// Plain English explanation, e.g. "Naked pair {3,7} in row 4 removes 3 from r4c9".
+    pub fn explain(&self) -> String {
+        let mut text = technique_title(self.technique);
+
+        if !self.digits.is_empty() {
+            text.push(' ');
+            text.push_str(&format_digits(&self.digits));
+        }
+
+        // A placement already names its cell, so only patterns behind eliminations are listed
+        if self.houses.is_empty() {
+            if self.placements.is_empty() && !self.pattern.is_empty() {
+                text.push_str(" on ");
+                text.push_str(&format_cells(&self.pattern));
+            }
+        } else {
+            let houses: Vec<String> = self.houses.iter().map(|house| house.to_string()).collect();
+            text.push_str(" in ");
+            text.push_str(&match (self.technique, houses.as_slice()) {
+                ("pointing_pairs", [b, line]) => format!("{} along {}", b, line),
+                ("box_line_reduction", [line, b]) => format!("{} within {}", line, b),
+                _ => join_and(&houses),
+            });
+        }
+
+        let placements: Vec<String> = self
+            .placements
+            .iter()
+            .map(|&(row, col, num)| format!("{} in {}", digit_char(num), format_cell((row, col))))
+            .collect();
+        if !placements.is_empty() {
+            text.push_str(" places ");
+            text.push_str(&join_and(&placements));
+        }
+
+        // Group eliminations by digit: "removes 3 from r4c9; 7 from r4c1, r4c2"
+        let mut removals: Vec<(u8, Vec<(usize, usize)>)> = Vec::new();
+        for &(row, col, num) in &self.eliminations {
+            match removals.iter_mut().find(|(n, _)| *n == num) {
+                Some((_, cells)) => cells.push((row, col)),
+                None => removals.push((num, vec![(row, col)])),
+            }
+        }
+        let removals: Vec<String> = removals
+            .iter()
+            .map(|(num, cells)| format!("{} from {}", digit_char(*num), format_cells(cells)))
+            .collect();
+        if !removals.is_empty() {
+            text.push_str(if placements.is_empty() {
+                " removes "
+            } else {
+                " and removes "
+            });
+            text.push_str(&removals.join("; "));
+        }
+
+        text
+    }
+}
+
+impl fmt::Display for SolveStep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.explain())
+    }
+}
+
+impl fmt::Display for House {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            House::Row(row) => write!(f, "row {}", row + 1),
+            House::Col(col) => write!(f, "column {}", col + 1),
+            House::Box(b) => write!(f, "box {}", b + 1),
+            House::Diagonal(0) => f.write_str("the main diagonal"),
+            House::Diagonal(_) => f.write_str("the anti-diagonal"),
+            House::Cage(cage) => write!(f, "cage {}", cage + 1),
+        }
+    }
+}
+
+impl SudokuBoard {
+    /// Every logical step applied so far, in order.
+    pub fn trace(&self) -> &[SolveStep] {
+        &self.trace
+    }
+
+    /// The trace explained one step per line.
+    pub fn explain_trace(&self) -> String {
+        self.trace
+            .iter()
+            .enumerate()
+            .map(|(i, step)| format!("{:>3}. {}\n", i + 1, step))
+            .collect()
+    }
+}
+
+/// Readable name for a technique, e.g. "naked_pairs" -> "Naked pair".
+fn technique_title(technique: &str) -> String {
+    let title = match technique {
+        "naked_singles" => "Naked single",
+        "hidden_singles" => "Hidden single",
+        "pointing_pairs" => "Pointing",
+        "box_line_reduction" => "Box/line reduction",
+        "naked_pairs" => "Naked pair",
+        "naked_triples" => "Naked triple",
+        "naked_quads" => "Naked quad",
+        "hidden_pairs" => "Hidden pair",
+        "hidden_triples" => "Hidden triple",
+        "hidden_quads" => "Hidden quad",
+        "x_wing" => "X-Wing",
+        "swordfish" => "Swordfish",
+        "jellyfish" => "Jellyfish",
+        "finned_x_wing" => "Finned X-Wing",
+        "finned_swordfish" => "Finned Swordfish",
+        "finned_jellyfish" => "Finned Jellyfish",
+        "sashimi_x_wing" => "Sashimi X-Wing",
+        "sashimi_swordfish" => "Sashimi Swordfish",
+        "sashimi_jellyfish" => "Sashimi Jellyfish",
+        "y_wing" => "Y-Wing",
+        "xyz_wing" => "XYZ-Wing",
+        "wxyz_wing" => "WXYZ-Wing",
+        "cage_combinations" => "Cage combinations",
+        "innies" => "Innies",
+        "outies" => "Outies",
+        "sandwich_sums" => "Sandwich",
+        "solution" => "Solution",
+        other => {
+            let mut title = other.replace('_', " ");
+            if let Some(first) = title.get_mut(0..1) {
+                first.make_ascii_uppercase();
+            }
+            return title;
+        }
+    };
+    title.to_string()
+}
+
+fn format_cell((row, col): (usize, usize)) -> String {
+    format!("r{}c{}", row + 1, col + 1)
+}
+
+fn format_cells(cells: &[(usize, usize)]) -> String {
+    cells
+        .iter()
+        .map(|&cell| format_cell(cell))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// "7" for a single digit, "{3,7}" for several.
+fn format_digits(digits: &[u8]) -> String {
+    if let [num] = digits {
+        return digit_char(*num).to_string();
+    }
+    let digits: Vec<String> = digits
+        .iter()
+        .map(|&num| digit_char(num).to_string())
+        .collect();
+    format!("{{{}}}", digits.join(","))
+}
+
+/// "a", "a and b", "a, b and c".
+fn join_and(items: &[String]) -> String {
+    match items {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
@@ -1 +1,63 @@
 use crate::prelude::*;
+
+/// The mask bit for digit `num`.
+pub fn bit(num: u8) -> Mask {
+    1 << (num - 1)
+}
+
+/// The character for a digit: `1`-`9`, then `A` for 10 up to `P` for 25.
+pub fn digit_char(num: u8) -> char {
+    match num {
+        1..=9 => char::from(b'0' + num),
+        _ => char::from(b'A' + num - 10),
+    }
+}
+
+/// The digit a character stands for, the inverse of `digit_char`, with letters in either case.
+/// `None` for `0` and anything else that isn't a digit of some board, from `1` to `P`.
+pub fn digit_value(c: char) -> Option<u8> {
+    match c.to_ascii_uppercase() {
+        '1'..='9' => Some(c as u8 - b'0'),
+        upper @ 'A'..='P' => Some(upper as u8 - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// The digits in `mask`, in ascending order.
+pub fn digits(mut mask: Mask) -> impl Iterator<Item = u8> {
+    std::iter::from_fn(move || {
+        if mask == 0 {
+            return None;
+        }
+        let num = mask.trailing_zeros() as u8 + 1;
+        mask &= mask - 1;
+        Some(num)
+    })
+}
+
+/// The zero-based positions of the bits set in `mask`, in ascending order.
+pub fn indices(mask: Mask) -> impl Iterator<Item = usize> {
+    digits(mask).map(|num| num as usize - 1)
+}
+
+/// Every subset of `mask` with exactly `size` bits set, without visiting the other subsets.
+pub fn subsets(mask: Mask, size: usize) -> impl Iterator<Item = Mask> {
+    // Gosper's hack steps through the `size`-bit combinations of the set bits' positions
+    let positions: Vec<usize> = indices(mask).collect();
+    let count = positions.len();
+    let mut next = (size <= count).then(|| (1u64 << size) - 1);
+    std::iter::from_fn(move || {
+        let combination = next.filter(|&combination| combination >> count == 0)?;
+        next = (combination != 0).then(|| {
+            let lowest = combination & combination.wrapping_neg();
+            let ripple = combination + lowest;
+            ripple | (((combination ^ ripple) >> 2) / lowest)
+        });
+        let subset = positions
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| combination >> i & 1 != 0)
+            .fold(0, |subset, (_, &position)| subset | 1 << position);
+        Some(subset)
+    })
+}